@@ -0,0 +1,129 @@
+use avian2d::prelude::*;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::asteroid::Asteroid;
+use crate::player::PlayerId;
+use crate::{GameState, GameplaySet};
+
+/// Size of the playable area, matching the background sprite
+pub const ARENA_SIZE: Vec2 = Vec2::new(1920.0, 1080.0);
+
+/// What happens to an asteroid leaving the arena
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AsteroidBounds {
+    Wrap,
+    #[default]
+    Despawn,
+}
+
+/// What happens to a player reaching the edge of the arena
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PlayerBounds {
+    Wrap,
+    #[default]
+    Clamp,
+}
+
+#[derive(Resource, Debug, Clone, Default)]
+pub struct ArenaBounds {
+    pub asteroids: AsteroidBounds,
+    pub players: PlayerBounds,
+}
+
+impl AsteroidBounds {
+    pub fn toggle(self) -> Self {
+        match self {
+            AsteroidBounds::Wrap => AsteroidBounds::Despawn,
+            AsteroidBounds::Despawn => AsteroidBounds::Wrap,
+        }
+    }
+}
+
+impl PlayerBounds {
+    pub fn toggle(self) -> Self {
+        match self {
+            PlayerBounds::Wrap => PlayerBounds::Clamp,
+            PlayerBounds::Clamp => PlayerBounds::Wrap,
+        }
+    }
+}
+
+/// Teleports the transform to the opposite side once it is further than `margin` outside the arena
+fn wrap_position(transform: &mut Mut<Transform>, margin: f32) {
+    let half_size = ARENA_SIZE / 2.0 + margin;
+    let position = transform.translation;
+    if position.x.abs() > half_size.x {
+        transform.translation.x = -position.x.signum() * half_size.x;
+    }
+    if position.y.abs() > half_size.y {
+        transform.translation.y = -position.y.signum() * half_size.y;
+    }
+}
+
+fn asteroids_bounds(
+    mut commands: Commands,
+    bounds: Res<ArenaBounds>,
+    mut asteroids_q: Query<(Entity, &mut Transform, &Asteroid)>,
+) {
+    for (asteroid, mut asteroid_trans, asteroid_info) in asteroids_q.iter_mut() {
+        // Only act when the asteroid is completely outside of the arena
+        let margin = asteroid_info.radius();
+
+        match bounds.asteroids {
+            AsteroidBounds::Wrap => wrap_position(&mut asteroid_trans, margin),
+            AsteroidBounds::Despawn => {
+                let half_size = ARENA_SIZE / 2.0 + margin;
+                let position = asteroid_trans.translation.truncate();
+                if position.x.abs() > half_size.x || position.y.abs() > half_size.y {
+                    commands.entity(asteroid).despawn_recursive();
+                }
+            }
+        }
+    }
+}
+
+fn players_bounds(
+    bounds: Res<ArenaBounds>,
+    mut players_q: Query<(&mut Transform, &mut LinearVelocity, &Sprite), With<PlayerId>>,
+) {
+    for (mut player_trans, mut player_vel, player_sprite) in players_q.iter_mut() {
+        let half_extent = player_sprite.custom_size.unwrap_or_default() / 2.0;
+
+        match bounds.players {
+            PlayerBounds::Wrap => wrap_position(&mut player_trans, half_extent.x),
+            PlayerBounds::Clamp => {
+                let max = ARENA_SIZE / 2.0 - half_extent;
+                let position = player_trans.translation.truncate();
+                let clamped = position.clamp(-max, max);
+                if clamped == position {
+                    continue;
+                }
+
+                // Stop the ship from pushing against the edge
+                if clamped.x != position.x {
+                    player_vel.x = 0.0;
+                }
+                if clamped.y != position.y {
+                    player_vel.y = 0.0;
+                }
+
+                player_trans.translation = clamped.extend(player_trans.translation.z);
+            }
+        }
+    }
+}
+
+pub struct ArenaPlugin;
+
+impl Plugin for ArenaPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ArenaBounds>().add_systems(
+            FixedUpdate,
+            (asteroids_bounds, players_bounds)
+                .chain()
+                .in_set(GameplaySet::Bounds)
+                .run_if(in_state(GameState::InGame)),
+        );
+    }
+}
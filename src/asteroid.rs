@@ -1,40 +1,345 @@
-use std::time::Duration;
+use std::collections::HashSet;
+use std::f32::consts::TAU;
 
 use avian2d::prelude::*;
+use bevy::ecs::system::{EntityCommands, SystemParam};
 use bevy::prelude::*;
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::sprite::MaterialMesh2dBundle;
 use bevy_hanabi::prelude::*;
 use bevy_prng::WyRand;
+use bevy_rand::component::EntropyComponent;
 use bevy_rand::resource::GlobalEntropy;
+use bevy_rand::traits::ForkableRng;
 use rand_core::RngCore;
+use serde::Deserialize;
 
-use crate::player::{GameLayer, PlayerId, PlayerLinkCollider};
-use crate::{AsteroidEffect, GameState, ScoreText};
+use crate::config::GameConfig;
+use crate::health::{Health, Invulnerable};
+use crate::kind::{AsteroidKinds, Chilled, KindEffects};
+use crate::player::{GameLayer, LinkTension, PlayerId, PlayerLink, PlayerLinkCollider, Wreck};
+use crate::powerup::{ActivePowerUps, PowerUpKind};
+use crate::wave::WaveList;
+use crate::{AsteroidEffect, GameState, GameplaySet, RunEntity, ScoreText, StartRun};
 
+/// Fires `remaining` shots, one every time the timer finishes, following its pattern
 #[derive(Component)]
-struct AsteroidSpawner {
-    timer: Timer,
+pub struct AsteroidSpawner {
+    /// Index used by the wave definitions to refer to this spawner
+    pub index: usize,
+    pub timer: Timer,
+    pub remaining: u32,
+    pub min_speed: f32,
+    pub max_speed: f32,
+    /// Link passes left before the spawner gets destroyed
+    pub health: u32,
+    pub pattern: FiringPattern,
+    /// Shots fired since the spawner opened, used by the spiral
+    pub shots: u32,
 }
 
+/// Where a spawner launches its asteroids
+#[derive(Deserialize, Debug, Clone, Default)]
+pub enum FiringPattern {
+    /// In any direction
+    #[default]
+    Random,
+    /// Toward the closest ship
+    AtNearestPlayer,
+    /// Toward the middle of the closest link
+    AtLinkMidpoint,
+    /// `count` asteroids at once, evenly spread around the spawner
+    Radial { count: u32 },
+    /// Turning by `step` radians after every shot
+    Spiral { step: f32 },
+    /// Where the closest ship will be when the asteroid reaches it, if it keeps its velocity
+    Lead,
+}
+
+/// How a spawner moves around the arena
+#[derive(Deserialize, Debug, Clone, Default)]
+pub enum SpawnerMotion {
+    #[default]
+    Fixed,
+    /// Circles around the center of the arena at the distance it starts from, `speed` being in
+    /// radians per second
+    Orbit { speed: f32 },
+    /// Goes through the points then back to its starting position, over and over
+    Path { points: Vec<Vec2>, speed: f32 },
+}
+
+/// Spawner described in the waves file
+#[derive(Deserialize, Debug, Clone)]
+pub struct SpawnerDefinition {
+    pub position: Vec2,
+    #[serde(default)]
+    pub motion: SpawnerMotion,
+    /// Link passes needed to destroy the spawner
+    #[serde(default = "default_spawner_health")]
+    pub health: u32,
+    #[serde(default)]
+    pub pattern: FiringPattern,
+}
+
+fn default_spawner_health() -> u32 {
+    3
+}
+
+/// Motion of a spawner and how far along it the spawner is
+#[derive(Component)]
+struct SpawnerPath {
+    motion: SpawnerMotion,
+    start: Vec2,
+    elapsed: f32,
+}
+
+impl SpawnerPath {
+    fn position(&self) -> Vec2 {
+        match &self.motion {
+            SpawnerMotion::Fixed => self.start,
+            SpawnerMotion::Orbit { speed } => {
+                Vec2::from_angle(speed * self.elapsed).rotate(self.start)
+            }
+            SpawnerMotion::Path { points, speed } => {
+                let waypoints = std::iter::once(self.start)
+                    .chain(points.iter().copied())
+                    .chain(std::iter::once(self.start))
+                    .collect::<Vec<_>>();
+                let loop_length = waypoints
+                    .windows(2)
+                    .map(|segment| segment[0].distance(segment[1]))
+                    .sum::<f32>();
+                if loop_length <= 0.0 {
+                    return self.start;
+                }
+                let mut distance = (speed * self.elapsed).rem_euclid(loop_length);
+                for segment in waypoints.windows(2) {
+                    let length = segment[0].distance(segment[1]);
+                    if distance <= length {
+                        return segment[0].lerp(segment[1], distance / length.max(f32::EPSILON));
+                    }
+                    distance -= length;
+                }
+                self.start
+            }
+        }
+    }
+}
+
+/// Spawner opening mid-game, it grows and can neither fire nor be hit until the timer finishes
+#[derive(Component)]
+struct SpawnerWarmUp(Timer);
+
+const SPAWNER_WARM_UP: f32 = 2.0;
+const SPAWNER_SIZE: f32 = 35.0;
+/// Scale of a spawner opening, the physics not handling bodies scaled down to nothing
+const SPAWNER_MIN_SCALE: f32 = 0.01;
+/// Score of a destroyed spawner
+const SPAWNER_SCORE: usize = 10;
+
+/// Particles swirling around the spawners
+#[derive(Resource)]
+pub struct PortalEffect(Handle<EffectAsset>);
+
+#[derive(Component, Clone, Copy)]
+pub struct Asteroid {
+    /// Size tier of the asteroid, `0` being the smallest one that gets destroyed by the link
+    tier: u8,
+    /// Index in `AsteroidKinds`
+    kind: usize,
+    /// Link passes left before the asteroid breaks
+    armor: u32,
+    /// Distance from the center to the farthest point of the outline
+    radius: f32,
+}
+
+impl Asteroid {
+    pub fn kind(&self) -> usize {
+        self.kind
+    }
+
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+}
+
+const MAX_ASTEROID_TIER: u8 = 2;
+/// Score multiplier of the asteroids destroyed by a whipping link
+const WHIP_SCORE_FACTOR: usize = 2;
+
+/// Fragments ignore the link for a short time so they are not cut again right after splitting
 #[derive(Component)]
-pub struct Asteroid;
+struct LinkImmunity(Timer);
 
 #[derive(Bundle)]
 struct AsteroidBundle {
-    sprite: SpriteBundle,
+    mesh: MaterialMesh2dBundle<ColorMaterial>,
     collider: Collider,
     sensor: Sensor,
     collision_layer: CollisionLayers,
     rigidbody: RigidBody,
     velocity: LinearVelocity,
+    angular_velocity: AngularVelocity,
     mass: MassPropertiesBundle,
     asteroid: Asteroid,
 }
 
-fn setup_spawner(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut effects: ResMut<Assets<EffectAsset>>,
-) {
+/// Solid collider of an asteroid, child of the asteroid so that its sensor still detects the ships
+/// and the link
+#[derive(Component)]
+struct AsteroidBody;
+
+/// Everything needed to spawn asteroids
+#[derive(SystemParam)]
+struct AsteroidAssets<'w> {
+    asset_server: Res<'w, AssetServer>,
+    meshes: ResMut<'w, Assets<Mesh>>,
+    materials: ResMut<'w, Assets<ColorMaterial>>,
+    kinds: Res<'w, AsteroidKinds>,
+}
+
+impl AsteroidBundle {
+    fn new(
+        assets: &mut AsteroidAssets,
+        position: Vec2,
+        velocity: Vec2,
+        tier: u8,
+        rng: &mut impl RngCore,
+        kind: usize,
+    ) -> Self {
+        let random_01 = random_01(rng);
+        let size = (30.0 + random_01 * 5.0) * (1.0 + 0.5 * tier as f32);
+        let kind_info = assets.kinds.get(kind);
+        let (collider, outline) = asteroid_outline(size / 2.0 * 0.8, rng);
+
+        Self {
+            mesh: MaterialMesh2dBundle {
+                mesh: assets.meshes.add(outline_mesh(&outline, size)).into(),
+                material: assets.materials.add(ColorMaterial {
+                    color: kind_info.color(),
+                    texture: Some(assets.asset_server.load("textures/asteroid.png")),
+                }),
+                transform: Transform::from_translation(position.extend(0.0)),
+                ..default()
+            },
+            mass: MassPropertiesBundle::new_computed(&collider, 1.0),
+            collider,
+            sensor: Sensor,
+            collision_layer: CollisionLayers::new(
+                GameLayer::Asteroid,
+                [GameLayer::Player, GameLayer::Link],
+            ),
+
+            rigidbody: RigidBody::Dynamic,
+            velocity: LinearVelocity(velocity),
+            angular_velocity: AngularVelocity(2.0 * random_01 - 1.0),
+            asteroid: Asteroid {
+                tier,
+                kind,
+                armor: kind_info.armor.max(1),
+                radius: outline
+                    .iter()
+                    .map(|point| point.length())
+                    .fold(0.0, f32::max),
+            },
+        }
+    }
+
+    /// Solid asteroids get a body bouncing off the other solid asteroids, and spin
+    fn spawn<'a>(mut self, commands: &'a mut Commands, solid: bool) -> EntityCommands<'a> {
+        if !solid {
+            self.angular_velocity = AngularVelocity::ZERO;
+        }
+        let body = self.collider.clone();
+        let mut asteroid = commands.spawn((self, RunEntity));
+        if solid {
+            asteroid.with_children(|parent| {
+                parent.spawn((
+                    TransformBundle::default(),
+                    body,
+                    // The mass comes from the asteroid bundle
+                    ColliderDensity(0.0),
+                    Restitution::new(0.8),
+                    CollisionLayers::new(GameLayer::SolidAsteroid, [GameLayer::SolidAsteroid]),
+                    AsteroidBody,
+                ));
+            });
+        }
+        asteroid
+    }
+}
+
+/// Irregular convex outline around the center of an asteroid, about `radius` away from it, with
+/// the matching collider
+fn asteroid_outline(radius: f32, rng: &mut impl RngCore) -> (Collider, Vec<Vec2>) {
+    const MIN_VERTICES: u32 = 7;
+    const MAX_VERTICES: u32 = 11;
+
+    let count = MIN_VERTICES + rng.next_u32() % (MAX_VERTICES - MIN_VERTICES + 1);
+    let points = (0..count)
+        .map(|i| {
+            let angle = (i as f32 + 0.8 * (random_01(rng) - 0.5)) / count as f32 * TAU;
+            Vec2::from_angle(angle) * radius * (0.8 + 0.35 * random_01(rng))
+        })
+        .collect::<Vec<_>>();
+
+    // Points inside the hull are dropped, so the mesh follows the collider
+    let Some(collider) = Collider::convex_hull(points.clone()) else {
+        return (Collider::circle(radius), points);
+    };
+    let outline = collider
+        .shape()
+        .as_convex_polygon()
+        .map(|polygon| {
+            polygon
+                .points()
+                .iter()
+                .map(|point| Vec2::new(point.x, point.y))
+                .collect()
+        })
+        .unwrap_or(points);
+    (collider, outline)
+}
+
+/// Fan of triangles filling a convex outline, the texture covering `size` around the center
+fn outline_mesh(outline: &[Vec2], size: f32) -> Mesh {
+    let positions = std::iter::once(Vec2::ZERO)
+        .chain(outline.iter().copied())
+        .collect::<Vec<_>>();
+    let uvs = positions
+        .iter()
+        .map(|position| [0.5 + position.x / size, 0.5 - position.y / size])
+        .collect::<Vec<_>>();
+    let count = outline.len() as u32;
+    let indices = (0..count)
+        .flat_map(|i| [0, i + 1, (i + 1) % count + 1])
+        .collect::<Vec<_>>();
+
+    Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::default(),
+    )
+    .with_inserted_attribute(
+        Mesh::ATTRIBUTE_NORMAL,
+        vec![[0.0, 0.0, 1.0]; positions.len()],
+    )
+    .with_inserted_attribute(
+        Mesh::ATTRIBUTE_POSITION,
+        positions
+            .iter()
+            .map(|position| position.extend(0.0).to_array())
+            .collect::<Vec<_>>(),
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
+    .with_inserted_indices(Indices::U32(indices))
+}
+
+fn random_01(rng: &mut impl RngCore) -> f32 {
+    (rng.next_u32() as f32) / (u32::MAX as f32)
+}
+
+fn setup_portal_effect(mut commands: Commands, mut effects: ResMut<Assets<EffectAsset>>) {
     let mut color_gradient1 = Gradient::new();
     color_gradient1.add_key(0.0, Vec4::new(4.0, 4.0, 4.0, 1.0));
     color_gradient1.add_key(0.1, Vec4::new(4.0, 4.0, 0.0, 1.0));
@@ -45,211 +350,364 @@ fn setup_spawner(
     size_gradient1.add_key(0.3, Vec2::new(0.2, 0.02));
     size_gradient1.add_key(1.0, Vec2::splat(0.0));
 
-    for x in -1..=1 {
-        for y in -1..=1 {
-            if x == 0 || y == 0 {
-                continue;
-            }
+    let writer = ExprWriter::new();
 
-            let position = Vec3::new((x as f32) * 200.0, (y as f32) * 200.0, 0.0);
+    let init_pos = SetPositionCircleModifier {
+        center: writer.lit(Vec3::ZERO).expr(),
+        axis: writer.lit(Vec3::Z).expr(),
+        radius: writer.lit(20.).expr(),
+        dimension: ShapeDimension::Surface,
+    };
 
-            let writer = ExprWriter::new();
+    let age = writer.lit(0.).expr();
+    let init_age = SetAttributeModifier::new(Attribute::AGE, age);
 
-            let init_pos = SetPositionCircleModifier {
-                center: writer.lit(Vec3::ZERO).expr(),
-                axis: writer.lit(Vec3::Z).expr(),
-                radius: writer.lit(20.).expr(),
-                dimension: ShapeDimension::Surface,
-            };
+    // Give a bit of variation by randomizing the lifetime per particle
+    let lifetime = writer.lit(3.0).uniform(writer.lit(4.0)).expr();
+    let init_lifetime = SetAttributeModifier::new(Attribute::LIFETIME, lifetime);
 
-            let age = writer.lit(0.).expr();
-            let init_age = SetAttributeModifier::new(Attribute::AGE, age);
-
-            // Give a bit of variation by randomizing the lifetime per particle
-            let lifetime = writer.lit(3.0).uniform(writer.lit(4.0)).expr();
-            let init_lifetime = SetAttributeModifier::new(Attribute::LIFETIME, lifetime);
-
-            // Add drag to make particles slow down a bit after the initial acceleration
-            let drag = writer.lit(15.).expr();
-            let update_drag = LinearDragModifier::new(drag);
-            let mut module = writer.finish();
-            let tangent_accel =
-                TangentAccelModifier::constant(&mut module, position, Vec3::Y, 300.);
-
-            let effect = effects.add(
-                EffectAsset::new(vec![16384, 16384], Spawner::rate(5000.0.into()), module)
-                    .with_name("portal")
-                    .init(init_pos)
-                    .init(init_age)
-                    .init(init_lifetime)
-                    .update(update_drag)
-                    .update(tangent_accel)
-                    .render(ColorOverLifetimeModifier {
-                        gradient: color_gradient1.clone(),
-                    })
-                    .render(SizeOverLifetimeModifier {
-                        gradient: size_gradient1.clone(),
-                        screen_space_size: false,
-                    })
-                    .render(OrientModifier::new(OrientMode::AlongVelocity)),
-            );
+    // Add drag to make particles slow down a bit after the initial acceleration
+    let drag = writer.lit(15.).expr();
+    let update_drag = LinearDragModifier::new(drag);
+    let mut module = writer.finish();
+    // Particles live in the frame of their spawner so they follow it when it moves
+    let tangent_accel = TangentAccelModifier::constant(&mut module, Vec3::ZERO, Vec3::Y, 300.);
+
+    let effect = effects.add(
+        EffectAsset::new(vec![16384, 16384], Spawner::rate(5000.0.into()), module)
+            .with_name("portal")
+            .with_simulation_space(SimulationSpace::Local)
+            .init(init_pos)
+            .init(init_age)
+            .init(init_lifetime)
+            .update(update_drag)
+            .update(tangent_accel)
+            .render(ColorOverLifetimeModifier {
+                gradient: color_gradient1,
+            })
+            .render(SizeOverLifetimeModifier {
+                gradient: size_gradient1,
+                screen_space_size: false,
+            })
+            .render(OrientModifier::new(OrientMode::AlongVelocity)),
+    );
+    commands.insert_resource(PortalEffect(effect));
+}
+
+/// Spawns a spawner at the start of its motion, growing out of a portal when `warm_up` is set
+pub fn spawn_spawner(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    portal_effect: &PortalEffect,
+    rng: &mut GlobalEntropy<WyRand>,
+    spawner: AsteroidSpawner,
+    definition: &SpawnerDefinition,
+    warm_up: bool,
+) {
+    let path = SpawnerPath {
+        motion: definition.motion.clone(),
+        start: definition.position,
+        elapsed: 0.0,
+    };
+    let scale = Vec3::splat(if warm_up { SPAWNER_MIN_SCALE } else { 1.0 });
+    let mut spawner_commands = commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                custom_size: Some(Vec2::splat(SPAWNER_SIZE)),
+                color: Color::srgba(0.5, 0.4, 0.6, 1.0),
+                ..default()
+            },
+            transform: Transform::from_translation(path.position().extend(-1.0)).with_scale(scale),
+            texture: asset_server.load("textures/spawner.png"),
+            ..default()
+        },
+        spawner,
+        path,
+        RigidBody::Kinematic,
+        Collider::circle(SPAWNER_SIZE / 2.0),
+        Sensor,
+        CollisionLayers::new(GameLayer::Spawner, [GameLayer::Link]),
+        // Each spawner has its own generator so the order they run in doesn't matter
+        rng.fork_rng(),
+        ParticleEffect::new(portal_effect.0.clone()),
+        CompiledParticleEffect::default(),
+        EffectProperties::default(),
+        RunEntity,
+    ));
+    if warm_up {
+        spawner_commands.insert(SpawnerWarmUp(Timer::from_seconds(
+            SPAWNER_WARM_UP,
+            TimerMode::Once,
+        )));
+    }
+}
 
-            commands.spawn((
-                SpriteBundle {
-                    sprite: Sprite {
-                        custom_size: Some(Vec2::splat(35.0)),
-                        color: Color::srgba(0.5, 0.4, 0.6, 1.0),
-                        ..default()
-                    },
-                    transform: Transform::from_xyz((x as f32) * 200.0, (y as f32) * 200.0, -1.0),
-                    texture: asset_server.load("textures/spawner.png"),
-                    ..default()
-                },
-                AsteroidSpawner {
-                    timer: Timer::new(Duration::from_secs_f32(3.0), TimerMode::Repeating),
-                },
-                ParticleEffect::new(effect.clone()),
-                CompiledParticleEffect::default(),
-                EffectProperties::default(),
-                // ParticleEffectBundle {
-                //     transform: Transform::from_translation(position),
-                //     ..default()
-                // },
-            ));
+fn setup_spawner(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    portal_effect: Res<PortalEffect>,
+    wave_list: Res<WaveList>,
+    mut rng: ResMut<GlobalEntropy<WyRand>>,
+) {
+    for (index, definition) in wave_list.spawners.iter().enumerate() {
+        spawn_spawner(
+            &mut commands,
+            &asset_server,
+            &portal_effect,
+            &mut rng,
+            AsteroidSpawner::idle(index, definition),
+            definition,
+            false,
+        );
+    }
+}
+
+impl AsteroidSpawner {
+    /// Spawner waiting for a wave to fire
+    pub fn idle(index: usize, definition: &SpawnerDefinition) -> Self {
+        Self {
+            index,
+            timer: Timer::default(),
+            remaining: 0,
+            min_speed: 0.0,
+            max_speed: 0.0,
+            health: definition.health,
+            pattern: definition.pattern.clone(),
+            shots: 0,
         }
     }
 }
 
-#[derive(Component)]
-struct HealthBar(f32);
+fn move_spawners(mut spawners: Query<(&mut Transform, &mut SpawnerPath)>, time: Res<Time>) {
+    for (mut spawner_trans, mut path) in spawners.iter_mut() {
+        path.elapsed += time.delta_seconds();
+        spawner_trans.translation = path.position().extend(spawner_trans.translation.z);
+    }
+}
 
-#[derive(Component)]
-pub struct HealthParent;
-
-const MAX_SIZE_HEALTHBAR: f32 = 180.0;
-
-fn setup_health_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
-    commands
-        .spawn((
-            NodeBundle {
-                style: Style {
-                    position_type: PositionType::Absolute,
-                    width: Val::Px(175.0),
-                    height: Val::Px(50.0),
-                    left: Val::Px(40.0),
-                    top: Val::Px(40.0),
-                    ..default()
-                },
+fn warm_up_spawners(
+    mut commands: Commands,
+    mut spawners: Query<(Entity, &mut Transform, &mut SpawnerWarmUp)>,
+    time: Res<Time>,
+) {
+    for (spawner, mut spawner_trans, mut warm_up) in spawners.iter_mut() {
+        if warm_up.0.tick(time.delta()).finished() {
+            spawner_trans.scale = Vec3::ONE;
+            commands.entity(spawner).remove::<SpawnerWarmUp>();
+        } else {
+            spawner_trans.scale = Vec3::splat(warm_up.0.fraction().max(SPAWNER_MIN_SCALE));
+        }
+    }
+}
+
+/// Every pass of the link through a spawner damages it, destroying it once out of health
+fn spawner_hits(
+    mut commands: Commands,
+    mut started_reader: EventReader<CollisionStarted>,
+    mut spawners: Query<
+        (&mut AsteroidSpawner, &Transform, &mut Sprite),
+        (Without<SpawnerWarmUp>, Without<ScoreText>),
+    >,
+    links: Query<(), With<PlayerLinkCollider>>,
+    mut score_q: Query<&mut ScoreText>,
+    asteroid_effect: Res<AsteroidEffect>,
+) {
+    for CollisionStarted(entity1, entity2) in started_reader.read() {
+        let spawner = match (links.contains(*entity1), links.contains(*entity2)) {
+            (true, false) => *entity2,
+            (false, true) => *entity1,
+            _ => continue,
+        };
+        let Ok((mut spawner_info, spawner_trans, mut sprite)) = spawners.get_mut(spawner) else {
+            continue;
+        };
+        if spawner_info.health == 0 {
+            continue;
+        }
+
+        spawner_info.health -= 1;
+        // Damaged spawners get redder
+        let damage = 1.0 / (spawner_info.health + 1) as f32;
+        sprite.color = Color::srgba(0.5 + 0.5 * (1.0 - damage), 0.4 * damage, 0.6 * damage, 1.0);
+        if spawner_info.health > 0 {
+            continue;
+        }
+
+        commands.spawn((
+            ParticleEffectBundle {
+                effect: ParticleEffect::new(asteroid_effect.0.clone()),
+                transform: Transform::from_translation(spawner_trans.translation),
                 ..default()
             },
-            HealthParent,
-        ))
-        .with_children(|parent| {
-            parent.spawn((
-                ImageBundle {
-                    style: Style {
-                        position_type: PositionType::Absolute,
-                        width: Val::Px(MAX_SIZE_HEALTHBAR),
-                        height: Val::Px(50.0),
-                        left: Val::Px(0.0),
-                        top: Val::Px(0.0),
-                        ..default()
-                    },
-                    image: asset_server.load("textures/ui/health.png").into(),
-                    ..default()
-                },
-                HealthBar(1.0),
-            ));
-            parent.spawn((
-                ImageBundle {
-                    style: Style {
-                        position_type: PositionType::Absolute,
-                        width: Val::Px(MAX_SIZE_HEALTHBAR),
-                        height: Val::Px(50.0),
-                        left: Val::Px(0.0),
-                        top: Val::Px(0.0),
-                        ..default()
-                    },
-                    image: asset_server.load("textures/ui/transparent.png").into(),
-                    ..default()
-                },
-                Outline {
-                    width: Val::Px(6.0),
-                    offset: Val::Px(0.0),
-                    color: Color::WHITE,
-                },
-            ));
-        });
-}
-
-fn update_health_ui(mut health_bar_query: Query<(&mut Style, &HealthBar), Changed<HealthBar>>) {
-    let Ok((mut health_style, health_bar)) = health_bar_query.get_single_mut() else {
-        return;
+            RunEntity,
+        ));
+        commands.entity(spawner).despawn_recursive();
+        score_q.iter_mut().for_each(|mut s| s.0 += SPAWNER_SCORE);
+    }
+}
+
+/// Sent when an asteroid hits a player
+#[derive(Event, Debug, Clone, Copy)]
+pub struct PlayerDamaged {
+    /// Health actually lost, from 0 to 1
+    pub damage: f32,
+}
+
+/// System handling the asteroids hitting the ships and the link
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AsteroidHitSet;
+
+/// Sent when the link destroys an asteroid
+#[derive(Event, Debug, Clone, Copy)]
+pub struct AsteroidDestroyed {
+    pub position: Vec2,
+}
+
+/// Direction in which a shot at `speed` from `origin` meets a target at `position` moving at
+/// `velocity`, if it can catch up with it
+fn intercept(origin: Vec2, position: Vec2, velocity: Vec2, speed: f32) -> Option<Vec2> {
+    let offset = position - origin;
+    // Solves |offset + velocity * t| = speed * t for the soonest positive t
+    let a = velocity.length_squared() - speed * speed;
+    let b = 2.0 * offset.dot(velocity);
+    let c = offset.length_squared();
+    let time = if a.abs() < f32::EPSILON {
+        (b.abs() > f32::EPSILON)
+            .then(|| -c / b)
+            .filter(|time| *time > 0.0)?
+    } else {
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let root = discriminant.sqrt();
+        [(-b - root) / (2.0 * a), (-b + root) / (2.0 * a)]
+            .into_iter()
+            .filter(|time| *time > 0.0)
+            .min_by(f32::total_cmp)?
     };
-    health_style.width = Val::Px(MAX_SIZE_HEALTHBAR * health_bar.0);
+    (offset + velocity * time).try_normalize()
 }
 
 fn asteroid_spawner(
-    mut spawner: Query<(&mut AsteroidSpawner, &Transform)>,
+    mut spawner: Query<
+        (
+            &mut AsteroidSpawner,
+            &Transform,
+            &mut EntropyComponent<WyRand>,
+        ),
+        Without<SpawnerWarmUp>,
+    >,
+    players: Query<(&Transform, &LinearVelocity), (With<PlayerId>, Without<Wreck>)>,
+    links: Query<&PlayerLink>,
     time: Res<Time>,
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut rng: ResMut<GlobalEntropy<WyRand>>,
+    mut assets: AsteroidAssets,
+    config: Res<GameConfig>,
 ) {
-    for (mut spawner_timer, spawner_trans) in spawner.iter_mut() {
-        spawner_timer.timer.tick(time.delta());
-
-        if spawner_timer.timer.finished() {
-            let new_timer_duration = spawner_timer
-                .timer
-                .duration()
-                .mul_f32(0.96)
-                .clamp(Duration::from_secs_f32(0.5), Duration::MAX);
-            spawner_timer.timer.set_duration(new_timer_duration);
-            spawner_timer.timer.reset();
+    for (mut spawner, spawner_trans, mut rng) in spawner.iter_mut() {
+        if spawner.remaining == 0 {
+            continue;
+        }
+
+        spawner.timer.tick(time.delta());
+
+        if spawner.timer.finished() {
+            spawner.remaining -= 1;
 
             let random_dir = Vec2 {
-                x: 2.0 * (rng.next_u32() as f32) / (u32::MAX as f32) - 1.0,
-                y: 2.0 * (rng.next_u32() as f32) / (u32::MAX as f32) - 1.0,
+                x: 2.0 * random_01(&mut *rng) - 1.0,
+                y: 2.0 * random_01(&mut *rng) - 1.0,
             }
             .normalize();
 
-            const MAX_ASTEROID_SPEED: f32 = 150.0;
-            const MIN_ASTEROID_SPEED: f32 = 100.0;
-            let random_01 = (rng.next_u32() as f32) / (u32::MAX as f32);
+            let random_01 = random_01(&mut *rng);
             let random_speed =
-                (random_01 * (MAX_ASTEROID_SPEED - MIN_ASTEROID_SPEED)) + MIN_ASTEROID_SPEED;
-            let random_size = 30.0 + random_01 * 5.0;
-
-            commands.spawn(AsteroidBundle {
-                sprite: SpriteBundle {
-                    sprite: Sprite {
-                        custom_size: Some(Vec2 {
-                            x: random_size,
-                            y: random_size,
-                        }),
-                        ..default()
-                    },
-                    transform: Transform::from_xyz(
-                        spawner_trans.translation.x,
-                        spawner_trans.translation.y,
-                        0.0,
-                    ),
-                    texture: asset_server.load("textures/asteroid.png"),
-                    ..default()
-                },
-                collider: Collider::circle(random_size / 2.0 * 0.8),
-                sensor: Sensor,
-                collision_layer: CollisionLayers::new(
-                    GameLayer::Asteroid,
-                    [GameLayer::Player, GameLayer::Link],
-                ),
-
-                rigidbody: RigidBody::Dynamic,
-                velocity: LinearVelocity(random_dir * random_speed),
-                mass: MassPropertiesBundle::new_computed(&Collider::circle(1.0), 1.0),
-                asteroid: Asteroid,
-            });
+                (random_01 * (spawner.max_speed - spawner.min_speed)) + spawner.min_speed;
+
+            let position = spawner_trans.translation.truncate();
+            let nearest_player = players
+                .iter()
+                .map(|(player_trans, player_vel)| {
+                    (player_trans.translation.truncate(), player_vel.0)
+                })
+                .min_by(|(a, _), (b, _)| {
+                    a.distance_squared(position)
+                        .total_cmp(&b.distance_squared(position))
+                });
+            let toward = |target: Vec2| (target - position).try_normalize();
+
+            // Aimed patterns fall back to a random direction without any target
+            let directions = match spawner.pattern {
+                FiringPattern::Random => vec![random_dir],
+                FiringPattern::AtNearestPlayer => {
+                    vec![nearest_player
+                        .and_then(|(target, _)| toward(target))
+                        .unwrap_or(random_dir)]
+                }
+                FiringPattern::AtLinkMidpoint => {
+                    let midpoint = links
+                        .iter()
+                        .filter_map(|link| {
+                            let (player1, player2) = link.players();
+                            let [(trans1, _), (trans2, _)] =
+                                players.get_many([player1, player2]).ok()?;
+                            Some(
+                                (trans1.translation.truncate() + trans2.translation.truncate())
+                                    / 2.0,
+                            )
+                        })
+                        .min_by(|a, b| {
+                            a.distance_squared(position)
+                                .total_cmp(&b.distance_squared(position))
+                        });
+                    vec![midpoint.and_then(toward).unwrap_or(random_dir)]
+                }
+                FiringPattern::Radial { count } => {
+                    let count = count.max(1);
+                    (0..count)
+                        .map(|i| Vec2::from_angle(std::f32::consts::TAU * i as f32 / count as f32))
+                        .map(|direction| direction.rotate(random_dir))
+                        .collect()
+                }
+                FiringPattern::Spiral { step } => {
+                    vec![Vec2::from_angle(step * spawner.shots as f32)]
+                }
+                FiringPattern::Lead => {
+                    vec![nearest_player
+                        .and_then(|(target, velocity)| {
+                            intercept(position, target, velocity, random_speed)
+                                .or_else(|| toward(target))
+                        })
+                        .unwrap_or(random_dir)]
+                }
+            };
+            spawner.shots += 1;
+
+            for direction in directions {
+                let kind = assets.kinds.pick(self::random_01(&mut *rng));
+                AsteroidBundle::new(
+                    &mut assets,
+                    position,
+                    direction * random_speed,
+                    MAX_ASTEROID_TIER,
+                    &mut *rng,
+                    kind,
+                )
+                .spawn(&mut commands, config.solid_asteroids);
+            }
+        }
+    }
+}
+
+fn tick_link_immunity(
+    mut commands: Commands,
+    mut immunity_q: Query<(Entity, &mut LinkImmunity)>,
+    time: Res<Time>,
+) {
+    for (entity, mut immunity) in immunity_q.iter_mut() {
+        if immunity.0.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<LinkImmunity>();
         }
     }
 }
@@ -257,43 +715,43 @@ fn asteroid_spawner(
 fn asteroid_trigger(
     mut commands: Commands,
     mut collision_event_reader: EventReader<Collision>,
+    mut started_reader: EventReader<CollisionStarted>,
     asteroids_q: Query<
-        (Entity, &Transform),
+        (
+            Entity,
+            &Transform,
+            &Asteroid,
+            &LinearVelocity,
+            Has<LinkImmunity>,
+        ),
         (
             Without<PlayerLinkCollider>,
             Without<PlayerId>,
             With<Asteroid>,
-            Without<HealthBar>,
             Without<ScoreText>,
         ),
     >,
-    players_q: Query<
-        Entity,
+    mut players_q: Query<
+        (
+            Entity,
+            &Transform,
+            &mut Health,
+            Has<Invulnerable>,
+            Has<Wreck>,
+        ),
         (
             Without<PlayerLinkCollider>,
             With<PlayerId>,
             Without<Asteroid>,
-            Without<HealthBar>,
             Without<ScoreText>,
         ),
     >,
     links_q: Query<
-        Entity,
+        &LinkTension,
         (
             With<PlayerLinkCollider>,
             Without<PlayerId>,
             Without<Asteroid>,
-            Without<HealthBar>,
-            Without<ScoreText>,
-        ),
-    >,
-    mut health_q: Query<
-        &mut HealthBar,
-        (
-            Without<PlayerLinkCollider>,
-            Without<PlayerId>,
-            Without<Asteroid>,
-            With<HealthBar>,
             Without<ScoreText>,
         ),
     >,
@@ -303,15 +761,33 @@ fn asteroid_trigger(
             Without<PlayerLinkCollider>,
             Without<PlayerId>,
             Without<Asteroid>,
-            Without<HealthBar>,
-            Without<HealthBar>,
         ),
     >,
-    mut end_state: ResMut<NextState<GameState>>,
+    player_links_q: Query<&PlayerLink>,
+    mut damaged_writer: EventWriter<PlayerDamaged>,
+    mut destroyed_writer: EventWriter<AsteroidDestroyed>,
+    power_ups: Res<ActivePowerUps>,
     asteroid_effect: Res<AsteroidEffect>,
+    kind_effects: Res<KindEffects>,
+    mut assets: AsteroidAssets,
+    mut rng: ResMut<GlobalEntropy<WyRand>>,
+    config: Res<GameConfig>,
 ) {
+    // An asteroid can touch several colliders in the same frame but must only be handled once
+    let mut handled_asteroids = HashSet::new();
+    // Ships only get damaged once per frame, the invulnerability starting on the next one
+    let mut hit_players = HashSet::new();
+    // Explosive asteroids destroyed this frame, with their position
+    let mut explosions = Vec::new();
+
+    // Colliders which just started touching each other, in both orders
+    let link_passes = started_reader
+        .read()
+        .flat_map(|CollisionStarted(entity1, entity2)| [(*entity1, *entity2), (*entity2, *entity1)])
+        .collect::<HashSet<_>>();
+
     for Collision(contacts) in collision_event_reader.read() {
-        let ((asteroid, asteroid_trans), other) = match (
+        let ((asteroid, asteroid_trans, asteroid_info, asteroid_vel, link_immune), other) = match (
             asteroids_q.get(contacts.entity1),
             asteroids_q.get(contacts.entity2),
         ) {
@@ -320,43 +796,380 @@ fn asteroid_trigger(
             _ => continue,
         };
 
-        let player = players_q.get(other).ok();
+        let player = players_q.get_mut(other).ok();
         let link = links_q.get(other).ok();
 
-        match (link, player) {
-            (None, Some(_)) => {
-                let Ok(mut health_bar) = health_q.get_single_mut() else {
-                    continue;
-                };
+        // Asteroids go through invulnerable ships and wrecks
+        let player_protected =
+            player
+                .as_ref()
+                .is_some_and(|(player, _, _, invulnerable, wreck)| {
+                    *invulnerable || *wreck || hit_players.contains(player)
+                });
+        if (link.is_some() && link_immune) || player_protected {
+            continue;
+        }
+        // Armored asteroids lose armor once per pass of the link, not on every frame touching it
+        if link.is_some() && asteroid_info.armor > 1 && !link_passes.contains(&(asteroid, other)) {
+            continue;
+        }
+        // Only the contacts handled below count, so a skipped one doesn't shield a ship
+        if (link.is_none() && player.is_none()) || !handled_asteroids.insert(asteroid) {
+            continue;
+        }
 
-                health_bar.0 = (health_bar.0 - 0.2).clamp(0.0, 1.0);
+        let kind = assets.kinds.get(asteroid_info.kind);
+        let effect = kind_effects
+            .get(asteroid_info.kind)
+            .unwrap_or_else(|| asteroid_effect.0.clone());
+        let position = asteroid_trans.translation.truncate();
+        // Armored asteroids go through the link, only exploding on the pass that breaks them
+        let breaks = link.is_none() || asteroid_info.armor <= 1;
+        if breaks && kind.explosion_radius > 0.0 {
+            explosions.push((position, kind.clone()));
+        }
+
+        match (link, player) {
+            (None, Some(_)) if power_ups.is_active(PowerUpKind::Shield) => {
+                // Shielded ships break the asteroids without scoring nor splitting them
+                spawn_effect(&mut commands, effect, asteroid_trans.translation);
                 commands.entity(asteroid).despawn_recursive();
-                if health_bar.0 == 0.0 {
-                    end_state.set(GameState::EndGame);
+            }
+            (None, Some((player, player_trans, mut health, ..))) => {
+                let away = (player_trans.translation - asteroid_trans.translation)
+                    .truncate()
+                    .normalize_or_zero();
+                damage_ship(
+                    &mut commands,
+                    &mut damaged_writer,
+                    &config,
+                    (player, &mut health),
+                    config.asteroid_damage,
+                    away,
+                );
+                hit_players.insert(player);
+                if kind.explosion_radius > 0.0 {
+                    spawn_effect(&mut commands, effect, asteroid_trans.translation);
                 }
+                commands.entity(asteroid).despawn_recursive();
             }
-            (Some(_), None) => {
-                commands.spawn(ParticleEffectBundle {
-                    effect: ParticleEffect::new(asteroid_effect.0.clone()),
-                    transform: Transform::from_translation(asteroid_trans.translation),
-                    ..default()
-                });
+            (Some(tension), None) => {
+                // Ice asteroids slow down both ships of the link they touch
+                if kind.chill_duration > 0.0 {
+                    if let Some(player_link) = player_links_q.iter().find(|l| l.collider() == other)
+                    {
+                        let (player1, player2) = player_link.players();
+                        for ship in [player1, player2] {
+                            commands
+                                .entity(ship)
+                                .try_insert(Chilled(Timer::from_seconds(
+                                    kind.chill_duration,
+                                    TimerMode::Once,
+                                )));
+                        }
+                    }
+                }
+
+                spawn_effect(&mut commands, effect, asteroid_trans.translation);
+                if asteroid_info.armor > 1 {
+                    commands.entity(asteroid).try_insert(Asteroid {
+                        armor: asteroid_info.armor - 1,
+                        ..*asteroid_info
+                    });
+                    continue;
+                }
+
                 commands.entity(asteroid).despawn_recursive();
-                score_q.iter_mut().for_each(|mut s| s.0 += 1);
+                destroyed_writer.send(AsteroidDestroyed { position });
+
+                // Smaller asteroids are harder to hit so they are worth more
+                let mut score = (1 + MAX_ASTEROID_TIER - asteroid_info.tier) as usize;
+                score *= kind.score_factor;
+                // A whipping link shatters the asteroid instead of splitting it
+                let whipped = tension.is_whipping();
+                if whipped {
+                    score *= WHIP_SCORE_FACTOR;
+                }
+                score_q.iter_mut().for_each(|mut s| s.0 += score);
+
+                if asteroid_info.tier == 0 || whipped {
+                    continue;
+                }
+
+                const FRAGMENT_SPREAD: f32 = std::f32::consts::FRAC_PI_2;
+                const FRAGMENT_SPEED_FACTOR: f32 = 1.3;
+                let fragment_count = 2 + rng.next_u32() % 2;
+                let velocity = asteroid_vel.0 * FRAGMENT_SPEED_FACTOR;
+                for i in 0..fragment_count {
+                    let angle = FRAGMENT_SPREAD * (i as f32 / (fragment_count - 1) as f32 - 0.5);
+                    let fragment_vel = Vec2::from_angle(angle).rotate(velocity);
+                    AsteroidBundle::new(
+                        &mut assets,
+                        position + fragment_vel.normalize_or_zero() * 10.0,
+                        fragment_vel,
+                        asteroid_info.tier - 1,
+                        &mut *rng,
+                        asteroid_info.kind,
+                    )
+                    .spawn(&mut commands, config.solid_asteroids)
+                    .insert(LinkImmunity(Timer::from_seconds(0.3, TimerMode::Once)));
+                }
             }
             (_, _) => continue,
         }
     }
+
+    // Explosions hurt every ship around them, except while shielded
+    if power_ups.is_active(PowerUpKind::Shield) {
+        return;
+    }
+    for (position, kind) in explosions {
+        for (player, player_trans, mut health, invulnerable, wreck) in players_q.iter_mut() {
+            let away = player_trans.translation.truncate() - position;
+            if invulnerable
+                || wreck
+                || hit_players.contains(&player)
+                || away.length() > kind.explosion_radius
+            {
+                continue;
+            }
+            damage_ship(
+                &mut commands,
+                &mut damaged_writer,
+                &config,
+                (player, &mut health),
+                kind.explosion_damage,
+                away.normalize_or_zero(),
+            );
+            hit_players.insert(player);
+        }
+    }
+}
+
+/// Solid asteroids hitting each other hard enough lose a size tier and shed a chip, the smallest
+/// ones crumbling
+fn chip_asteroids(
+    mut commands: Commands,
+    mut collision_event_reader: EventReader<Collision>,
+    bodies: Query<(), With<AsteroidBody>>,
+    asteroids: Query<(&Transform, &Asteroid, &LinearVelocity, &Mass)>,
+    asteroid_effect: Res<AsteroidEffect>,
+    kind_effects: Res<KindEffects>,
+    mut assets: AsteroidAssets,
+    mut rng: ResMut<GlobalEntropy<WyRand>>,
+    config: Res<GameConfig>,
+) {
+    // An asteroid hitting several others in the same frame only chips once
+    let mut chipped = HashSet::new();
+
+    for Collision(contacts) in collision_event_reader.read() {
+        if !bodies.contains(contacts.entity1) || !bodies.contains(contacts.entity2) {
+            continue;
+        }
+        for body in [contacts.body_entity1, contacts.body_entity2]
+            .into_iter()
+            .flatten()
+        {
+            let Ok((asteroid_trans, asteroid_info, velocity, mass)) = asteroids.get(body) else {
+                continue;
+            };
+            // The impulse of the collision gives the speed change of each asteroid
+            let speed_change = contacts.total_normal_impulse.abs() / mass.0.max(f32::EPSILON);
+            if speed_change < config.asteroid_chip_speed || !chipped.insert(body) {
+                continue;
+            }
+
+            let effect = kind_effects
+                .get(asteroid_info.kind)
+                .unwrap_or_else(|| asteroid_effect.0.clone());
+            spawn_effect(&mut commands, effect, asteroid_trans.translation);
+            commands.entity(body).despawn_recursive();
+            if asteroid_info.tier == 0 {
+                continue;
+            }
+
+            // The chip flies off sideways, far enough not to overlap the rest of the asteroid
+            const CHIP_SPEED: f32 = 60.0;
+            let position = asteroid_trans.translation.truncate();
+            let side = velocity.0.perp().try_normalize().unwrap_or(Vec2::X);
+            for (tier, offset, chip_velocity) in [
+                (asteroid_info.tier - 1, -18.0, -CHIP_SPEED * 0.3),
+                (0, 24.0, CHIP_SPEED),
+            ] {
+                AsteroidBundle::new(
+                    &mut assets,
+                    position + side * offset,
+                    velocity.0 + side * chip_velocity,
+                    tier,
+                    &mut *rng,
+                    asteroid_info.kind,
+                )
+                .spawn(&mut commands, true);
+            }
+        }
+    }
+}
+
+fn spawn_effect(commands: &mut Commands, effect: Handle<EffectAsset>, translation: Vec3) {
+    commands.spawn((
+        ParticleEffectBundle {
+            effect: ParticleEffect::new(effect),
+            transform: Transform::from_translation(translation),
+            ..default()
+        },
+        RunEntity,
+    ));
+}
+
+/// Hurts a ship, knocking it `away` and making it invulnerable for a while
+fn damage_ship(
+    commands: &mut Commands,
+    damaged_writer: &mut EventWriter<PlayerDamaged>,
+    config: &GameConfig,
+    (ship, health): (Entity, &mut Health),
+    damage: f32,
+    away: Vec2,
+) {
+    let remaining = (health.0 - damage).clamp(0.0, 1.0);
+    damaged_writer.send(PlayerDamaged {
+        damage: health.0 - remaining,
+    });
+    health.0 = remaining;
+
+    commands.entity(ship).try_insert((
+        Invulnerable(Timer::from_seconds(
+            config.invulnerability_duration,
+            TimerMode::Once,
+        )),
+        ExternalImpulse::new(away * config.knockback_impulse),
+    ));
 }
 
 pub struct AsteroidPlugin;
 
 impl Plugin for AsteroidPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, setup_spawner)
-            .add_systems(Startup, setup_health_ui)
-            .add_systems(Update, update_health_ui)
-            .add_systems(Update, asteroid_trigger)
-            .add_systems(Update, asteroid_spawner);
+        app.add_event::<PlayerDamaged>()
+            .add_event::<AsteroidDestroyed>()
+            .add_systems(Startup, setup_portal_effect)
+            .add_systems(StartRun, setup_spawner)
+            .add_systems(
+                FixedUpdate,
+                (
+                    (asteroid_trigger, chip_asteroids)
+                        .chain()
+                        .in_set(AsteroidHitSet),
+                    spawner_hits.in_set(GameplaySet::Hits).after(AsteroidHitSet),
+                    asteroid_spawner.in_set(GameplaySet::Spawn),
+                    (tick_link_immunity, move_spawners, warm_up_spawners)
+                        .chain()
+                        .in_set(GameplaySet::Motion),
+                )
+                    .run_if(in_state(GameState::InGame)),
+            )
+            .configure_sets(FixedUpdate, AsteroidHitSet.in_set(GameplaySet::Hits));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand_core::SeedableRng;
+
+    use super::*;
+
+    /// Whether a shot fired in `direction` meets the target at some point
+    fn meets(origin: Vec2, position: Vec2, velocity: Vec2, speed: f32, direction: Vec2) -> bool {
+        // Time at which the target crosses the line of the shot
+        let offset = position - origin;
+        let time = -direction.perp_dot(offset) / direction.perp_dot(velocity);
+        time > 0.0 && ((offset + velocity * time).length() - speed * time).abs() < 1e-2
+    }
+
+    #[test]
+    fn intercept_still_target() {
+        let direction = intercept(Vec2::ZERO, Vec2::new(0.0, 10.0), Vec2::ZERO, 5.0);
+        assert_eq!(direction, Some(Vec2::Y));
+    }
+
+    #[test]
+    fn intercept_leads_moving_target() {
+        let (origin, position, velocity) = (Vec2::ZERO, Vec2::new(100.0, 0.0), Vec2::Y * 30.0);
+        let direction = intercept(origin, position, velocity, 50.0).unwrap();
+        assert!(direction.y > 0.0);
+        assert!(meets(origin, position, velocity, 50.0, direction));
+    }
+
+    #[test]
+    fn intercept_target_as_fast_as_shot() {
+        // Coming toward the origin, the target is met half way
+        let direction = intercept(Vec2::ZERO, Vec2::new(10.0, 0.0), Vec2::NEG_X * 5.0, 5.0);
+        assert_eq!(direction, Some(Vec2::X));
+        // Fleeing, it can never be caught
+        let direction = intercept(Vec2::ZERO, Vec2::new(10.0, 0.0), Vec2::X * 5.0, 5.0);
+        assert_eq!(direction, None);
+    }
+
+    #[test]
+    fn intercept_faster_target() {
+        assert_eq!(
+            intercept(Vec2::ZERO, Vec2::new(10.0, 0.0), Vec2::X * 20.0, 5.0),
+            None
+        );
+        // A faster target coming closer can still be met
+        let (origin, position, velocity) = (Vec2::ZERO, Vec2::new(100.0, 10.0), Vec2::NEG_X * 20.0);
+        let direction = intercept(origin, position, velocity, 5.0).unwrap();
+        assert!(meets(origin, position, velocity, 5.0, direction));
+    }
+
+    fn outline(seed: u64) -> (Collider, Vec<Vec2>) {
+        asteroid_outline(30.0, &mut WyRand::seed_from_u64(seed))
+    }
+
+    #[test]
+    fn outline_is_convex() {
+        for seed in 0..50 {
+            let (_, outline) = outline(seed);
+            assert!(outline.len() >= 3);
+            for (i, point) in outline.iter().enumerate() {
+                let next = outline[(i + 1) % outline.len()];
+                let after = outline[(i + 2) % outline.len()];
+                // Counterclockwise, every corner turns left
+                assert!((next - *point).perp_dot(after - next) > 0.0);
+                assert!((24.0..=34.5).contains(&point.length()));
+            }
+        }
+    }
+
+    #[test]
+    fn outline_matches_collider() {
+        for seed in 0..50 {
+            let (collider, outline) = outline(seed);
+            let polygon = collider.shape().as_convex_polygon().unwrap();
+            let points = polygon
+                .points()
+                .iter()
+                .map(|point| Vec2::new(point.x, point.y))
+                .collect::<Vec<_>>();
+            assert_eq!(points, outline);
+        }
+    }
+
+    #[test]
+    fn outline_follows_seed() {
+        assert_eq!(outline(7).1, outline(7).1);
+        assert_ne!(outline(7).1, outline(8).1);
+    }
+
+    #[test]
+    fn outline_mesh_fans_around_center() {
+        let (_, outline) = outline(3);
+        let mesh = outline_mesh(&outline, 60.0);
+        assert_eq!(mesh.count_vertices(), outline.len() + 1);
+        let Some(Indices::U32(indices)) = mesh.indices() else {
+            panic!("indices should be 32 bits");
+        };
+        assert_eq!(indices.len(), outline.len() * 3);
+        assert!(indices.chunks(3).all(|triangle| triangle[0] == 0));
+        assert_eq!(indices[indices.len() - 1], 1);
     }
 }
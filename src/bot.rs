@@ -0,0 +1,260 @@
+use std::str::FromStr;
+
+use avian2d::prelude::*;
+use bevy::prelude::*;
+
+use crate::asteroid::Asteroid;
+use crate::input::{ApplyInputSet, PlayerDevice, PlayerInput};
+use crate::player::{PlayerId, PlayerLink, Wreck};
+use crate::{GameState, StartRun};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BotDifficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+/// How well a bot plays
+struct BotPreset {
+    /// Fixed steps between two decisions, the bot keeps its input in between
+    reaction_steps: u32,
+    /// Seconds ahead the bot predicts where the asteroids go
+    look_ahead: f32,
+    /// Distance under which the bot dodges an asteroid instead of sweeping it
+    dodge_distance: f32,
+    /// Speed at which the bot moves toward its target
+    cruise_speed: f32,
+    boost: bool,
+}
+
+impl BotDifficulty {
+    pub const ALL: [BotDifficulty; 3] = [
+        BotDifficulty::Easy,
+        BotDifficulty::Normal,
+        BotDifficulty::Hard,
+    ];
+
+    fn preset(self) -> BotPreset {
+        match self {
+            BotDifficulty::Easy => BotPreset {
+                reaction_steps: 16,
+                look_ahead: 0.3,
+                dodge_distance: 40.0,
+                cruise_speed: 250.0,
+                boost: false,
+            },
+            BotDifficulty::Normal => BotPreset {
+                reaction_steps: 8,
+                look_ahead: 0.6,
+                dodge_distance: 60.0,
+                cruise_speed: 400.0,
+                boost: false,
+            },
+            BotDifficulty::Hard => BotPreset {
+                reaction_steps: 2,
+                look_ahead: 1.0,
+                dodge_distance: 80.0,
+                cruise_speed: 600.0,
+                boost: true,
+            },
+        }
+    }
+}
+
+impl FromStr for BotDifficulty {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "easy" => Ok(BotDifficulty::Easy),
+            "normal" => Ok(BotDifficulty::Normal),
+            "hard" => Ok(BotDifficulty::Hard),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Input steering a ship at `position` going at `velocity` toward `target`
+fn steer(position: Vec2, velocity: Vec2, target: Vec2, preset: &BotPreset) -> PlayerInput {
+    const ARRIVAL_DISTANCE: f32 = 30.0;
+
+    let to_target = target - position;
+    // Slow down when getting close so the bot doesn't overshoot its target
+    let desired_velocity =
+        to_target.normalize_or_zero() * preset.cruise_speed.min(to_target.length() * 2.0);
+    let correction = desired_velocity - velocity;
+
+    if to_target.length() < ARRIVAL_DISTANCE {
+        return PlayerInput {
+            brake: velocity.length() > preset.cruise_speed / 4.0,
+            ..default()
+        };
+    }
+    PlayerInput {
+        aim: Some(correction).filter(|correction| *correction != Vec2::ZERO),
+        thrust: correction.length() > preset.cruise_speed / 10.0,
+        ..default()
+    }
+}
+
+/// Fixed steps played in the current game, the bots reacting on a fraction of them
+#[derive(Resource, Debug, Default)]
+struct BotSteps(u32);
+
+fn reset_bot_steps(mut steps: ResMut<BotSteps>) {
+    steps.0 = 0;
+}
+
+/// Bots dodge the asteroids about to hit them and otherwise move so that the link crosses the
+/// path of the asteroid closest to it, or go revive their wrecked partners
+fn bot_control(
+    mut steps: ResMut<BotSteps>,
+    mut bots: Query<(
+        Entity,
+        &PlayerDevice,
+        &Transform,
+        &LinearVelocity,
+        &mut PlayerInput,
+    )>,
+    players: Query<&Transform, With<PlayerId>>,
+    links: Query<&PlayerLink>,
+    wrecks: Query<(&Transform, &Wreck)>,
+    asteroids: Query<(&Transform, &LinearVelocity, &Asteroid)>,
+) {
+    const SWEEP_OVERSHOOT: f32 = 80.0;
+
+    let step = steps.0;
+    steps.0 += 1;
+
+    for (bot, device, bot_trans, bot_vel, mut bot_input) in bots.iter_mut() {
+        let PlayerDevice::Bot(difficulty) = device else {
+            continue;
+        };
+        let preset = difficulty.preset();
+        if step % preset.reaction_steps != 0 {
+            continue;
+        }
+
+        let position = bot_trans.translation.truncate();
+        let velocity = bot_vel.0;
+
+        // Closest approach of every asteroid within the look ahead, the soonest threat first
+        let threat = asteroids
+            .iter()
+            .filter_map(|(asteroid_trans, asteroid_vel, asteroid)| {
+                let radius = asteroid.radius();
+                let offset = asteroid_trans.translation.truncate() - position;
+                let relative_vel = asteroid_vel.0 - velocity;
+                let time = (-offset.dot(relative_vel) / relative_vel.length_squared().max(1.0))
+                    .clamp(0.0, preset.look_ahead);
+                let closest = offset + relative_vel * time;
+                (closest.length() < preset.dodge_distance + radius).then_some((
+                    time,
+                    closest,
+                    relative_vel,
+                ))
+            })
+            .min_by(|(time1, ..), (time2, ..)| time1.total_cmp(time2));
+
+        if let Some((_, closest, relative_vel)) = threat {
+            let away = (-closest)
+                .try_normalize()
+                .unwrap_or_else(|| relative_vel.perp().normalize_or_zero());
+            *bot_input = PlayerInput {
+                aim: Some(away).filter(|away| *away != Vec2::ZERO),
+                thrust: true,
+                boost: preset.boost,
+                ..default()
+            };
+            continue;
+        }
+
+        let partner = links
+            .iter()
+            .filter_map(|link| link.partner(bot))
+            .find_map(|partner| players.get(partner).ok())
+            .map(|partner_trans| partner_trans.translation.truncate());
+
+        let target = match partner {
+            Some(partner) => {
+                let middle = (position + partner) / 2.0;
+                let predicted = asteroids
+                    .iter()
+                    .map(|(asteroid_trans, asteroid_vel, _)| {
+                        asteroid_trans.translation.truncate() + asteroid_vel.0 * preset.look_ahead
+                    })
+                    .min_by(|a, b| {
+                        a.distance_squared(middle)
+                            .total_cmp(&b.distance_squared(middle))
+                    });
+                // Going past the asteroid, seen from the partner, puts the link across it
+                predicted.map_or(position, |predicted| {
+                    predicted + (predicted - partner).normalize_or_zero() * SWEEP_OVERSHOOT
+                })
+            }
+            // Without a link a bot goes to revive its partners, or can only stay alive
+            None => wrecks
+                .iter()
+                .filter(|(_, wreck)| wreck.partners.contains(&bot))
+                .map(|(wreck_trans, _)| wreck_trans.translation.truncate())
+                .min_by(|a, b| {
+                    a.distance_squared(position)
+                        .total_cmp(&b.distance_squared(position))
+                })
+                .unwrap_or(Vec2::ZERO),
+        };
+
+        *bot_input = steer(position, velocity, target, &preset);
+    }
+}
+
+pub struct BotPlugin;
+
+impl Plugin for BotPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BotSteps>()
+            .add_systems(StartRun, reset_bot_steps)
+            .add_systems(
+                FixedUpdate,
+                bot_control
+                    .before(ApplyInputSet)
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn difficulty_from_str() {
+        assert_eq!("easy".parse(), Ok(BotDifficulty::Easy));
+        assert_eq!("Normal".parse(), Ok(BotDifficulty::Normal));
+        assert_eq!("HARD".parse(), Ok(BotDifficulty::Hard));
+        assert_eq!("".parse::<BotDifficulty>(), Err(()));
+        assert_eq!("expert".parse::<BotDifficulty>(), Err(()));
+    }
+
+    #[test]
+    fn presets_get_harder() {
+        for pair in BotDifficulty::ALL.windows(2) {
+            let (easier, harder) = (pair[0].preset(), pair[1].preset());
+            assert!(harder.reaction_steps < easier.reaction_steps);
+            assert!(harder.look_ahead > easier.look_ahead);
+            assert!(harder.dodge_distance > easier.dodge_distance);
+        }
+    }
+
+    #[test]
+    fn steer_brakes_on_arrival() {
+        let preset = BotDifficulty::Normal.preset();
+        let input = steer(Vec2::ZERO, Vec2::X * 300.0, Vec2::new(10.0, 0.0), &preset);
+        assert!(input.brake && !input.thrust);
+
+        let input = steer(Vec2::ZERO, Vec2::ZERO, Vec2::new(0.0, 500.0), &preset);
+        assert!(input.thrust);
+        assert_eq!(input.aim.map(Vec2::normalize), Some(Vec2::Y));
+    }
+}
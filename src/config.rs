@@ -0,0 +1,198 @@
+use std::marker::PhantomData;
+
+use bevy::asset::io::file::FileAssetReader;
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, AsyncReadExt, LoadContext};
+use bevy::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::health::GameOverRule;
+use crate::player::LinkMode;
+use crate::powerup::PowerUpKind;
+
+/// Gameplay tuning, loaded from `assets/config/game.config.ron` and reloaded when the file changes
+///
+/// Values used when spawning (spawn radius, link width) apply from the next game.
+#[derive(Asset, Resource, TypePath, Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct GameConfig {
+    /// Distance of the players from the center of the arena when a game starts
+    pub spawn_radius: f32,
+    /// Maximum distance between two linked players
+    pub player_joint_distance: f32,
+    pub link_width: f32,
+    /// Leash or spring, applies from the next game
+    pub link_mode: LinkMode,
+    /// Softness of the joint in the spring mode
+    pub link_spring_compliance: f32,
+    /// Speed at which the ships must move apart to whip the link
+    pub link_whip_speed: f32,
+    /// Seconds a whip lasts, asteroids cut meanwhile are shattered and worth more
+    pub link_whip_duration: f32,
+    /// Health lost when an asteroid hits a ship, its health going from 0 to 1
+    pub asteroid_damage: f32,
+    /// Seconds during which a ship that was just hit can't be hit again
+    pub invulnerability_duration: f32,
+    /// Impulse pushing a ship away from the asteroid that hit it
+    pub knockback_impulse: f32,
+    /// Asteroids bounce off each other and spin instead of going through each other
+    pub solid_asteroids: bool,
+    /// Speed change over which colliding solid asteroids chip
+    pub asteroid_chip_speed: f32,
+    /// Distance under which a partner revives a wreck
+    pub revive_distance: f32,
+    /// Seconds a partner has to stay close to a wreck to revive it
+    pub revive_duration: f32,
+    /// Health of a revived ship
+    pub revive_health: f32,
+    /// Link energy used by every asteroid the link destroys, the energy going from 0 to 1
+    pub link_energy_per_kill: f32,
+    /// Fraction of the maximum distance past which a link drains energy
+    pub link_stretch_threshold: f32,
+    /// Energy drained per second by each stretched link
+    pub link_stretch_drain: f32,
+    /// Energy recovered per second by an idle link
+    pub link_energy_recharge: f32,
+    /// Energy recovered per second by a link turned off or overheated
+    pub link_off_recharge: f32,
+    /// Energy an overheated link needs to recover before working again
+    pub link_overheat_recovery: f32,
+    /// Chance for a destroyed asteroid to drop each kind of power-up, from 0 to 1
+    pub power_up_drop_chances: Vec<(PowerUpKind, f32)>,
+    /// Seconds a collected power-up lasts
+    pub power_up_duration: f32,
+    /// Seconds a dropped power-up waits to be collected
+    pub power_up_lifetime: f32,
+    pub game_over_rule: GameOverRule,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        Self {
+            spawn_radius: 100.0,
+            player_joint_distance: 200.0,
+            link_width: 10.0,
+            link_mode: LinkMode::default(),
+            link_spring_compliance: 0.1,
+            link_whip_speed: 400.0,
+            link_whip_duration: 0.3,
+            asteroid_damage: 0.2,
+            invulnerability_duration: 1.0,
+            knockback_impulse: 1500.0,
+            solid_asteroids: false,
+            asteroid_chip_speed: 150.0,
+            revive_distance: 80.0,
+            revive_duration: 3.0,
+            revive_health: 0.4,
+            link_energy_per_kill: 0.04,
+            link_stretch_threshold: 0.85,
+            link_stretch_drain: 0.3,
+            link_energy_recharge: 0.1,
+            link_off_recharge: 0.25,
+            link_overheat_recovery: 0.5,
+            power_up_drop_chances: vec![
+                (PowerUpKind::LongerLink, 0.02),
+                (PowerUpKind::WiderLink, 0.02),
+                (PowerUpKind::Shield, 0.015),
+                (PowerUpKind::SlowMotion, 0.015),
+                (PowerUpKind::Heal, 0.02),
+            ],
+            power_up_duration: 8.0,
+            power_up_lifetime: 10.0,
+            game_over_rule: GameOverRule::default(),
+        }
+    }
+}
+
+/// Loads RON files into assets of type `A`
+struct RonAssetLoader<A> {
+    extension: [&'static str; 1],
+    _marker: PhantomData<A>,
+}
+
+impl<A: Asset + DeserializeOwned> AssetLoader for RonAssetLoader<A> {
+    type Asset = A;
+    type Settings = ();
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<A, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &self.extension
+    }
+}
+
+#[derive(Resource)]
+struct RonAssetHandle<A: Asset>(Handle<A>);
+
+/// Copies the loaded asset into its resource every time it is (re)loaded
+fn update_ron_resource<A: Asset + Resource + Clone>(
+    mut asset_events: EventReader<AssetEvent<A>>,
+    assets: Res<Assets<A>>,
+    handle: Res<RonAssetHandle<A>>,
+    mut resource: ResMut<A>,
+) {
+    for event in asset_events.read() {
+        if !event.is_loaded_with_dependencies(&handle.0) && !event.is_modified(&handle.0) {
+            continue;
+        }
+        if let Some(loaded) = assets.get(&handle.0) {
+            info!("{} loaded", A::short_type_path());
+            *resource = loaded.clone();
+        }
+    }
+}
+
+/// Reads an asset file right away, so that a game started before the asset server is done
+/// doesn't use the default values
+fn read_ron_file<A: DeserializeOwned + Default>(path: &str) -> A {
+    let path = FileAssetReader::get_base_path().join("assets").join(path);
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(err) => {
+            warn!("Could not read {}: {err}", path.display());
+            return A::default();
+        }
+    };
+    ron::from_str(&content).unwrap_or_else(|err| {
+        warn!("Invalid file {}: {err}", path.display());
+        A::default()
+    })
+}
+
+/// Makes a resource loaded from a RON asset and reloaded every time the file changes
+///
+/// The extension must be unique to the asset type so that hot reloading finds the right loader.
+pub fn init_ron_resource<A>(app: &mut App, path: &'static str, extension: &'static str)
+where
+    A: Asset + Resource + Clone + Default + DeserializeOwned,
+{
+    app.init_asset::<A>()
+        .register_asset_loader(RonAssetLoader::<A> {
+            extension: [extension],
+            _marker: PhantomData,
+        })
+        .insert_resource(read_ron_file::<A>(path));
+
+    let handle = app.world().resource::<AssetServer>().load::<A>(path);
+    app.insert_resource(RonAssetHandle(handle))
+        .add_systems(PreUpdate, update_ron_resource::<A>);
+}
+
+pub struct ConfigPlugin;
+
+impl Plugin for ConfigPlugin {
+    fn build(&self, app: &mut App) {
+        init_ron_resource::<GameConfig>(app, "config/game.config.ron", "config.ron");
+    }
+}
@@ -0,0 +1,167 @@
+use avian2d::prelude::*;
+use bevy::prelude::*;
+
+use crate::asteroid::AsteroidDestroyed;
+use crate::config::GameConfig;
+use crate::input::PlayerInput;
+use crate::player::{
+    CustomMaterial, GameLayer, PlayerId, PlayerLink, PlayerLinkCollider, LINK_COLOR, MAX_PLAYERS,
+};
+use crate::powerup::ActivePowerUps;
+use crate::{GameState, GameplaySet, StartRun};
+
+/// Energy shared by every link, drained by kills and by stretching the links
+#[derive(Resource, Debug)]
+pub struct LinkEnergy {
+    /// From 0 to 1
+    pub energy: f32,
+    /// Turned on and off by the players
+    pub enabled: bool,
+    /// Set once the energy runs out, until it recovers enough
+    pub overheated: bool,
+    /// Whether each player held the toggle button on the previous step
+    held: [bool; MAX_PLAYERS as usize],
+}
+
+impl Default for LinkEnergy {
+    fn default() -> Self {
+        Self {
+            energy: 1.0,
+            enabled: true,
+            overheated: false,
+            held: [false; MAX_PLAYERS as usize],
+        }
+    }
+}
+
+impl LinkEnergy {
+    /// Whether the links destroy the asteroids
+    pub fn is_active(&self) -> bool {
+        self.enabled && !self.overheated
+    }
+}
+
+fn reset_link_energy(mut link_energy: ResMut<LinkEnergy>) {
+    *link_energy = LinkEnergy::default();
+}
+
+/// Any player pressing the toggle button turns every link on or off
+fn toggle_link(mut link_energy: ResMut<LinkEnergy>, players: Query<(&PlayerId, &PlayerInput)>) {
+    for (player_id, player_input) in players.iter() {
+        let held = &mut link_energy.held[player_id.0 as usize % MAX_PLAYERS as usize];
+        let pressed = player_input.toggle_link && !*held;
+        *held = player_input.toggle_link;
+        if pressed {
+            link_energy.enabled = !link_energy.enabled;
+        }
+    }
+}
+
+fn update_link_energy(
+    mut link_energy: ResMut<LinkEnergy>,
+    mut destroyed_reader: EventReader<AsteroidDestroyed>,
+    links: Query<&PlayerLink>,
+    players: Query<&Transform, With<PlayerId>>,
+    power_ups: Res<ActivePowerUps>,
+    config: Res<GameConfig>,
+    time: Res<Time>,
+) {
+    let kills = destroyed_reader.read().count();
+
+    let max_distance = config.player_joint_distance * power_ups.joint_distance_factor();
+    let stretched = links
+        .iter()
+        .filter_map(|link| {
+            let (player1, player2) = link.players();
+            players.get_many([player1, player2]).ok()
+        })
+        .filter(|[trans1, trans2]| {
+            trans1.translation.distance(trans2.translation)
+                > max_distance * config.link_stretch_threshold
+        })
+        .count();
+
+    let drain = if link_energy.is_active() {
+        kills as f32 * config.link_energy_per_kill
+            + stretched as f32 * config.link_stretch_drain * time.delta_seconds()
+    } else {
+        0.0
+    };
+    // An idle link recharges, faster when turned off or overheated
+    let recharge = if drain > 0.0 {
+        0.0
+    } else if link_energy.is_active() {
+        config.link_energy_recharge * time.delta_seconds()
+    } else {
+        config.link_off_recharge * time.delta_seconds()
+    };
+    link_energy.energy = (link_energy.energy - drain + recharge).clamp(0.0, 1.0);
+
+    if link_energy.energy <= 0.0 {
+        link_energy.overheated = true;
+    } else if link_energy.energy >= config.link_overheat_recovery {
+        link_energy.overheated = false;
+    }
+}
+
+/// Inactive links stop colliding with the asteroids and the spawners
+fn update_link_colliders(
+    link_energy: Res<LinkEnergy>,
+    mut colliders: Query<&mut CollisionLayers, With<PlayerLinkCollider>>,
+) {
+    let layers = if link_energy.is_active() {
+        CollisionLayers::new(GameLayer::Link, [GameLayer::Asteroid, GameLayer::Spawner])
+    } else {
+        CollisionLayers::new(GameLayer::Link, LayerMask::NONE)
+    };
+    for mut collision_layers in colliders.iter_mut() {
+        if *collision_layers != layers {
+            *collision_layers = layers;
+        }
+    }
+}
+
+/// Links fade as they lose energy, turn red once overheated and almost vanish when turned off
+fn update_link_color(
+    link_energy: Res<LinkEnergy>,
+    links: Query<&Handle<CustomMaterial>, With<PlayerLink>>,
+    mut materials: ResMut<Assets<CustomMaterial>>,
+) {
+    const OVERHEAT_COLOR: LinearRgba = LinearRgba::new(190.0, 30.0, 10.0, 1.0);
+    const OFF_COLOR: LinearRgba = LinearRgba::new(5.0, 19.0, 7.5, 0.3);
+
+    let color = if !link_energy.enabled {
+        OFF_COLOR
+    } else if link_energy.overheated {
+        OVERHEAT_COLOR * (0.2 + 0.3 * link_energy.energy)
+    } else {
+        LINK_COLOR * (0.3 + 0.7 * link_energy.energy)
+    };
+    for handle in links.iter() {
+        if materials.get(handle).is_some_and(|m| m.color != color) {
+            if let Some(material) = materials.get_mut(handle) {
+                material.color = color;
+            }
+        }
+    }
+}
+
+pub struct LinkEnergyPlugin;
+
+impl Plugin for LinkEnergyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LinkEnergy>()
+            .add_systems(StartRun, reset_link_energy)
+            .add_systems(
+                FixedUpdate,
+                (
+                    toggle_link.in_set(GameplaySet::Controls),
+                    (update_link_energy, update_link_colliders)
+                        .chain()
+                        .in_set(GameplaySet::Energy),
+                )
+                    .run_if(in_state(GameState::InGame)),
+            )
+            .add_systems(Update, update_link_color);
+    }
+}
@@ -0,0 +1,216 @@
+use std::f32::consts::TAU;
+use std::time::Duration;
+
+use avian2d::prelude::*;
+use bevy::app::AppExit;
+use bevy::input::InputPlugin;
+use bevy::prelude::*;
+use bevy::scene::ScenePlugin;
+use bevy::state::app::StatesPlugin;
+use bevy::time::TimeUpdateStrategy;
+use bevy_hanabi::EffectAsset;
+use serde::Serialize;
+
+use crate::asteroid::PlayerDamaged;
+use crate::bot::BotDifficulty;
+use crate::input::{ApplyInputSet, PlayerDevice, PlayerInput};
+use crate::player::{PlayerId, PlayersConfig, MAX_PLAYERS, MIN_PLAYERS};
+use crate::{add_gameplay, parse_cli_arg, CliSeed, GameState, RunSeed, ScoreText, FIXED_HZ};
+
+/// Simulated seconds after which a session stops if the players are still alive
+const DEFAULT_DURATION: f32 = 120.0;
+
+/// Result of a session, printed as JSON
+#[derive(Resource, Serialize, Debug, Clone, PartialEq)]
+struct SessionReport {
+    seed: u64,
+    score: usize,
+    /// Simulated seconds before the game over, or the whole session when the players survived
+    survival_time: f32,
+    survived: bool,
+    damage_taken: f32,
+}
+
+#[derive(Resource, Debug)]
+struct Session {
+    steps: u32,
+    max_steps: u32,
+    damage_taken: f32,
+}
+
+/// Runs a single game without window, rendering nor audio and prints its report
+///
+/// Every update advances the game by exactly one fixed step, as fast as the machine allows.
+pub fn run(seed: u64) {
+    let duration =
+        parse_cli_arg::<f32>("--duration", "a number of seconds").unwrap_or(DEFAULT_DURATION);
+    let number_players = parse_cli_arg::<u8>("--players", "a number of players")
+        .map(|n| n.clamp(MIN_PLAYERS, MAX_PLAYERS));
+    let bot = parse_cli_arg::<BotDifficulty>("--bot", "easy, normal or hard");
+
+    session_app(seed, duration, number_players, bot).run();
+}
+
+/// App simulating a game of `duration` seconds, the report being inserted as a resource at the end
+fn session_app(
+    seed: u64,
+    duration: f32,
+    number_players: Option<u8>,
+    bot: Option<BotDifficulty>,
+) -> App {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        StatesPlugin,
+        AssetPlugin::default(),
+        // Avian looks for colliders in the scenes
+        ScenePlugin,
+        HierarchyPlugin,
+        TransformPlugin,
+        InputPlugin,
+        PhysicsPlugins::new(FixedPostUpdate),
+    ))
+    // The gameplay entities hold handles to these assets even though nothing draws them
+    .init_asset::<Image>()
+    .init_asset::<Mesh>()
+    .init_asset::<ColorMaterial>()
+    .init_asset::<EffectAsset>();
+
+    add_gameplay(&mut app)
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+            1.0 / FIXED_HZ,
+        )))
+        .insert_resource(RunSeed(seed))
+        .insert_resource(CliSeed(Some(seed)))
+        .insert_resource(Session {
+            steps: 0,
+            max_steps: (duration as f64 * FIXED_HZ) as u32,
+            damage_taken: 0.0,
+        })
+        .add_systems(
+            Startup,
+            move |mut players_config: ResMut<PlayersConfig>,
+                  mut next_state: ResMut<NextState<GameState>>| {
+                if let Some(number_players) = number_players {
+                    players_config.number_players = number_players;
+                }
+                if let Some(bot) = bot {
+                    players_config.devices = [PlayerDevice::Bot(bot); MAX_PLAYERS as usize];
+                }
+                next_state.set(GameState::InGame);
+            },
+        )
+        .add_systems(
+            FixedUpdate,
+            scripted_pilots
+                .before(ApplyInputSet)
+                .run_if(in_state(GameState::InGame))
+                .run_if(move || bot.is_none()),
+        )
+        .add_systems(
+            FixedPostUpdate,
+            track_session.run_if(in_state(GameState::InGame)),
+        )
+        .add_systems(OnEnter(GameState::GameOver), report_session);
+    app
+}
+
+/// Without `--bot`, players fly in circles, each one starting in a different direction
+fn scripted_pilots(time: Res<Time>, mut players: Query<(&PlayerId, &mut PlayerInput)>) {
+    const TURN_SPEED: f32 = 1.5;
+
+    let number_players = players.iter().len() as f32;
+    for (player_id, mut player_input) in players.iter_mut() {
+        let angle = time.elapsed_seconds() * TURN_SPEED + player_id.0 as f32 * TAU / number_players;
+        *player_input = PlayerInput {
+            aim: Some(Vec2::from_angle(angle)),
+            thrust: true,
+            ..default()
+        };
+    }
+}
+
+fn track_session(
+    mut session: ResMut<Session>,
+    mut damaged_reader: EventReader<PlayerDamaged>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    session.damage_taken += damaged_reader.read().map(|event| event.damage).sum::<f32>();
+    session.steps += 1;
+    if session.steps >= session.max_steps {
+        next_state.set(GameState::GameOver);
+    }
+}
+
+fn report_session(
+    mut commands: Commands,
+    session: Res<Session>,
+    run_seed: Res<RunSeed>,
+    score_q: Query<&ScoreText>,
+    mut exit_writer: EventWriter<AppExit>,
+) {
+    let report = SessionReport {
+        seed: run_seed.0,
+        score: score_q.iter().map(|score| score.0).sum(),
+        survival_time: (session.steps as f64 / FIXED_HZ) as f32,
+        survived: session.steps >= session.max_steps,
+        damage_taken: session.damage_taken,
+    };
+
+    match serde_json::to_string(&report) {
+        Ok(json) => println!("{json}"),
+        Err(err) => eprintln!("Could not serialize the report: {err}"),
+    }
+    commands.insert_resource(report);
+    exit_writer.send(AppExit::Success);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::asteroid::Asteroid;
+
+    /// Report of a short session, with where the ships and asteroids ended up
+    fn simulate(seed: u64, bot: Option<BotDifficulty>) -> (SessionReport, Vec<Vec3>, Vec<Vec3>) {
+        let mut app = session_app(seed, 20.0, Some(3), bot);
+        app.finish();
+        app.cleanup();
+        while !app.world().contains_resource::<SessionReport>() {
+            app.update();
+        }
+
+        let world = app.world_mut();
+        let mut ships = world
+            .query::<(&PlayerId, &Transform)>()
+            .iter(world)
+            .map(|(player_id, transform)| (player_id.0, transform.translation))
+            .collect::<Vec<_>>();
+        ships.sort_by_key(|(player_id, _)| *player_id);
+        let asteroids = world
+            .query_filtered::<&Transform, With<Asteroid>>()
+            .iter(world)
+            .map(|transform| transform.translation)
+            .collect();
+        (
+            world.resource::<SessionReport>().clone(),
+            ships.into_iter().map(|(_, position)| position).collect(),
+            asteroids,
+        )
+    }
+
+    #[test]
+    fn scripted_session_is_deterministic() {
+        let first = simulate(7, None);
+        assert_eq!(first.1.len(), 3);
+        assert!(!first.2.is_empty());
+        assert_eq!(first, simulate(7, None));
+    }
+
+    #[test]
+    fn bot_session_is_deterministic() {
+        let first = simulate(11, Some(BotDifficulty::Hard));
+        assert_eq!(first, simulate(11, Some(BotDifficulty::Hard)));
+        assert_ne!(first.2, simulate(12, Some(BotDifficulty::Hard)).2);
+    }
+}
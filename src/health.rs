@@ -0,0 +1,254 @@
+use avian2d::prelude::*;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use bevy_hanabi::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::config::GameConfig;
+use crate::player::{PlayerId, PlayerLink, PlayersConfig, Wreck, MAX_PLAYERS, MIN_PLAYERS};
+use crate::{AsteroidEffect, GameState, GameplaySet, RunEntity, StartRun};
+
+/// Health of a ship, from 0 to 1, the ship becoming a wreck once it reaches 0
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Health(pub f32);
+
+/// Ship that was just hit and can't be hit again until the timer finishes
+#[derive(Component, Debug)]
+pub struct Invulnerable(pub Timer);
+
+/// When a game ends as ships get wrecked
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GameOverRule {
+    /// Once every ship is wrecked
+    #[default]
+    AllShipsDestroyed,
+    /// As soon as a ship is wrecked
+    AnyShipDestroyed,
+    /// Once no two ships are linked anymore
+    NoLinkLeft,
+}
+
+/// Bar showing the health of the player with this index
+#[derive(Component)]
+struct HealthBar(u8);
+
+const MAX_SIZE_HEALTHBAR: f32 = 180.0;
+
+fn setup_health_ui(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    players_config: Res<PlayersConfig>,
+) {
+    let number_players = players_config
+        .number_players
+        .clamp(MIN_PLAYERS, MAX_PLAYERS);
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    left: Val::Px(40.0),
+                    top: Val::Px(40.0),
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(16.0),
+                    ..default()
+                },
+                ..default()
+            },
+            RunEntity,
+        ))
+        .with_children(|parent| {
+            for i in 0..number_players {
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            align_items: AlignItems::Center,
+                            column_gap: Val::Px(16.0),
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .with_children(|row| {
+                        row.spawn(TextBundle::from_section(
+                            format!("P{}", i + 1),
+                            TextStyle {
+                                font_size: 30.0,
+                                ..default()
+                            },
+                        ));
+                        row.spawn(NodeBundle {
+                            style: Style {
+                                width: Val::Px(MAX_SIZE_HEALTHBAR),
+                                height: Val::Px(30.0),
+                                ..default()
+                            },
+                            ..default()
+                        })
+                        .with_children(|bar| {
+                            bar.spawn((
+                                ImageBundle {
+                                    style: Style {
+                                        position_type: PositionType::Absolute,
+                                        width: Val::Px(MAX_SIZE_HEALTHBAR),
+                                        height: Val::Percent(100.0),
+                                        ..default()
+                                    },
+                                    image: asset_server.load("textures/ui/health.png").into(),
+                                    ..default()
+                                },
+                                HealthBar(i),
+                            ));
+                            bar.spawn((
+                                ImageBundle {
+                                    style: Style {
+                                        position_type: PositionType::Absolute,
+                                        width: Val::Percent(100.0),
+                                        height: Val::Percent(100.0),
+                                        ..default()
+                                    },
+                                    image: asset_server.load("textures/ui/transparent.png").into(),
+                                    ..default()
+                                },
+                                Outline {
+                                    width: Val::Px(4.0),
+                                    offset: Val::Px(0.0),
+                                    color: Color::WHITE,
+                                },
+                            ));
+                        });
+                    });
+            }
+        });
+}
+
+fn update_health_ui(
+    players: Query<(&PlayerId, &Health)>,
+    mut health_bars: Query<(&mut Style, &HealthBar)>,
+) {
+    for (mut bar_style, health_bar) in health_bars.iter_mut() {
+        let health = players
+            .iter()
+            .find(|(player_id, _)| player_id.0 == health_bar.0)
+            .map_or(0.0, |(_, health)| health.0);
+        let width = Val::Px(MAX_SIZE_HEALTHBAR * health);
+        if bar_style.width != width {
+            bar_style.width = width;
+        }
+    }
+}
+
+fn tick_invulnerability(
+    mut commands: Commands,
+    mut ships: Query<(Entity, &mut Invulnerable, &mut Visibility)>,
+    time: Res<Time>,
+) {
+    for (ship, mut invulnerable, mut visibility) in ships.iter_mut() {
+        if invulnerable.0.tick(time.delta()).finished() {
+            commands.entity(ship).remove::<Invulnerable>();
+            *visibility = Visibility::Inherited;
+        }
+    }
+}
+
+fn blink_invulnerable_ships(mut ships: Query<(&Invulnerable, &mut Visibility)>) {
+    const BLINK_PERIOD: f32 = 0.1;
+
+    for (invulnerable, mut visibility) in ships.iter_mut() {
+        let visible = (invulnerable.0.elapsed_secs() / BLINK_PERIOD) as u32 % 2 == 0;
+        let blink = if visible {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+        if *visibility != blink {
+            *visibility = blink;
+        }
+    }
+}
+
+/// Turns the ships without health into wrecks, breaking their links, then ends the game if the
+/// rule says so
+fn wreck_ships(
+    mut commands: Commands,
+    players: Query<(Entity, &Transform, &Health), (With<PlayerId>, Without<Wreck>)>,
+    links: Query<(Entity, &PlayerLink)>,
+    joints: Query<(Entity, &DistanceJoint)>,
+    asteroid_effect: Res<AsteroidEffect>,
+    config: Res<GameConfig>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let mut wrecked = players
+        .iter()
+        .filter(|(_, _, health)| health.0 <= 0.0)
+        .map(|(ship, ship_trans, _)| {
+            commands.spawn((
+                ParticleEffectBundle {
+                    effect: ParticleEffect::new(asteroid_effect.0.clone()),
+                    transform: Transform::from_translation(ship_trans.translation),
+                    ..default()
+                },
+                RunEntity,
+            ));
+            (ship, Vec::new())
+        })
+        .collect::<HashMap<_, _>>();
+
+    if wrecked.is_empty() {
+        return;
+    }
+
+    let mut links_left = 0;
+    for (link, player_link) in links.iter() {
+        let (player1, player2) = player_link.players();
+        if !wrecked.contains_key(&player1) && !wrecked.contains_key(&player2) {
+            links_left += 1;
+            continue;
+        }
+        // Both ends remember each other so that either one can come back for the other
+        for (wreck, partner) in [(player1, player2), (player2, player1)] {
+            if let Some(partners) = wrecked.get_mut(&wreck) {
+                partners.push(partner);
+            }
+        }
+        commands.entity(player_link.collider()).despawn_recursive();
+        commands.entity(link).despawn_recursive();
+    }
+    for (joint_entity, joint) in joints.iter() {
+        if wrecked.contains_key(&joint.entity1) || wrecked.contains_key(&joint.entity2) {
+            commands.entity(joint_entity).despawn_recursive();
+        }
+    }
+
+    let ships_left = players.iter().len() - wrecked.len();
+    for (ship, partners) in wrecked.drain() {
+        commands.entity(ship).insert(Wreck::new(partners));
+    }
+
+    let game_over = ships_left == 0
+        || match config.game_over_rule {
+            GameOverRule::AllShipsDestroyed => false,
+            GameOverRule::AnyShipDestroyed => true,
+            GameOverRule::NoLinkLeft => links_left == 0,
+        };
+    if game_over {
+        next_state.set(GameState::GameOver);
+    }
+}
+
+pub struct HealthPlugin;
+
+impl Plugin for HealthPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(StartRun, setup_health_ui)
+            .add_systems(Update, (update_health_ui, blink_invulnerable_ships))
+            .add_systems(
+                FixedUpdate,
+                (
+                    tick_invulnerability.in_set(GameplaySet::Motion),
+                    wreck_ships.in_set(GameplaySet::Wrecks),
+                )
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
@@ -0,0 +1,248 @@
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{GameState, GameplaySet, StartRun};
+
+/// Number of scores kept in the table
+pub const MAX_HIGH_SCORES: usize = 10;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HighScoreEntry {
+    pub name: String,
+    pub score: usize,
+    pub seed: u64,
+    /// Seconds since the Unix epoch
+    pub date: u64,
+    /// Seconds
+    pub survival_time: f32,
+    pub number_players: u8,
+}
+
+impl HighScoreEntry {
+    pub fn now(score: usize, seed: u64, survival_time: f32, number_players: u8) -> Self {
+        Self {
+            name: String::new(),
+            score,
+            seed,
+            date: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |date| date.as_secs()),
+            survival_time,
+            number_players,
+        }
+    }
+}
+
+/// Best scores, from the highest to the lowest, saved to the data directory
+#[derive(Resource, Serialize, Deserialize, Debug, Clone, Default)]
+pub struct HighScores {
+    pub entries: Vec<HighScoreEntry>,
+}
+
+impl HighScores {
+    fn path() -> PathBuf {
+        dirs::data_dir()
+            .unwrap_or_default()
+            .join("bevy_asteroid")
+            .join("highscores.ron")
+    }
+
+    /// Loads the scores from the data file, starting with an empty table without it
+    fn load() -> Self {
+        let path = Self::path();
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            return Self::default();
+        };
+
+        match ron::from_str::<HighScores>(&content) {
+            Ok(mut high_scores) => {
+                high_scores.entries.sort_by(|a, b| b.score.cmp(&a.score));
+                high_scores.entries.truncate(MAX_HIGH_SCORES);
+                high_scores
+            }
+            Err(err) => {
+                warn!("Invalid high scores file {}: {err}", path.display());
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save(&self) {
+        let path = Self::path();
+        let content = match ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()) {
+            Ok(content) => content,
+            Err(err) => {
+                error!("Could not serialize the high scores: {err}");
+                return;
+            }
+        };
+
+        if let Err(err) = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(&path, content))
+        {
+            error!(
+                "Could not save the high scores to {}: {err}",
+                path.display()
+            );
+        }
+    }
+
+    /// Whether a game with this score enters the table
+    pub fn qualifies(&self, score: usize) -> bool {
+        score > 0
+            && (self.entries.len() < MAX_HIGH_SCORES
+                || self.entries.last().is_some_and(|last| score > last.score))
+    }
+
+    /// Adds the entry below the ones with the same score and returns its rank
+    pub fn insert(&mut self, entry: HighScoreEntry) -> usize {
+        let rank = self
+            .entries
+            .iter()
+            .position(|other| entry.score > other.score)
+            .unwrap_or(self.entries.len());
+        self.entries.insert(rank, entry);
+        self.entries.truncate(MAX_HIGH_SCORES);
+        rank
+    }
+
+    /// Text of the table, with a marker in front of the `highlight`-th entry
+    pub fn table(&self, highlight: Option<usize>) -> String {
+        let rows = self
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                let seconds = entry.survival_time as u32;
+                format!(
+                    "{}{:>2}. {:<12} {:>5}   {}P   {}:{:02}   {}   seed {}\n",
+                    if highlight == Some(i) { "> " } else { "  " },
+                    i + 1,
+                    entry.name,
+                    entry.score,
+                    entry.number_players,
+                    seconds / 60,
+                    seconds % 60,
+                    format_date(entry.date),
+                    entry.seed,
+                )
+            })
+            .collect::<String>();
+
+        if rows.is_empty() {
+            "HIGH SCORES\n\n  No score yet".to_string()
+        } else {
+            format!("HIGH SCORES\n\n{rows}")
+        }
+    }
+}
+
+/// `YYYY-MM-DD` date of a Unix timestamp, in UTC
+fn format_date(unix_seconds: u64) -> String {
+    // Converts days since the epoch to a civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let days = (unix_seconds / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    format!("{year}-{month:02}-{day:02}")
+}
+
+/// Seconds the players have survived in the current game
+#[derive(Resource, Debug, Default)]
+pub struct SurvivalTime(pub f32);
+
+fn reset_survival_time(mut survival_time: ResMut<SurvivalTime>) {
+    survival_time.0 = 0.0;
+}
+
+fn tick_survival_time(mut survival_time: ResMut<SurvivalTime>, time: Res<Time>) {
+    survival_time.0 += time.delta_seconds();
+}
+
+pub struct HighScorePlugin;
+
+impl Plugin for HighScorePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(HighScores::load())
+            .init_resource::<SurvivalTime>()
+            .add_systems(StartRun, reset_survival_time)
+            .add_systems(
+                FixedUpdate,
+                tick_survival_time
+                    .in_set(GameplaySet::Motion)
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, score: usize) -> HighScoreEntry {
+        HighScoreEntry {
+            name: name.to_string(),
+            ..HighScoreEntry::now(score, 0, 0.0, 2)
+        }
+    }
+
+    fn names(high_scores: &HighScores) -> Vec<&str> {
+        high_scores
+            .entries
+            .iter()
+            .map(|entry| entry.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn insert_keeps_scores_sorted() {
+        let mut high_scores = HighScores::default();
+        assert_eq!(high_scores.insert(entry("b", 20)), 0);
+        assert_eq!(high_scores.insert(entry("a", 30)), 0);
+        assert_eq!(high_scores.insert(entry("c", 10)), 2);
+        // Ties go below the existing entries
+        assert_eq!(high_scores.insert(entry("d", 20)), 2);
+        assert_eq!(names(&high_scores), ["a", "b", "d", "c"]);
+    }
+
+    #[test]
+    fn insert_truncates_table() {
+        let mut high_scores = HighScores::default();
+        for score in 1..=MAX_HIGH_SCORES {
+            high_scores.insert(entry("old", score));
+        }
+        assert_eq!(high_scores.insert(entry("new", 100)), 0);
+        assert_eq!(high_scores.entries.len(), MAX_HIGH_SCORES);
+        assert_eq!(high_scores.entries.last().unwrap().score, 2);
+    }
+
+    #[test]
+    fn qualifies() {
+        let mut high_scores = HighScores::default();
+        assert!(!high_scores.qualifies(0));
+        assert!(high_scores.qualifies(1));
+
+        for score in 1..=MAX_HIGH_SCORES {
+            high_scores.insert(entry("old", score * 10));
+        }
+        // A full table needs a score above its lowest one
+        assert!(!high_scores.qualifies(5));
+        assert!(!high_scores.qualifies(10));
+        assert!(high_scores.qualifies(11));
+    }
+}
@@ -0,0 +1,456 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use bevy::input::gamepad::{GamepadConnection, GamepadEvent};
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use serde::{Deserialize, Serialize};
+
+use crate::bot::BotDifficulty;
+use crate::player::{PlayerId, MAX_PLAYERS};
+use crate::GameState;
+
+/// Something a player can do, whatever the device used
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Action {
+    Rotate,
+    Thrust,
+    Brake,
+    Boost,
+    /// Turns the link on and off
+    ToggleLink,
+    Pause,
+}
+
+impl Action {
+    /// Actions bound to a button, [`Action::Rotate`] being bound to an axis
+    pub const BUTTONS: [Action; 5] = [
+        Action::Thrust,
+        Action::Brake,
+        Action::Boost,
+        Action::ToggleLink,
+        Action::Pause,
+    ];
+}
+
+/// Keys used to aim the ship on the keyboard
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DirectionKeys {
+    pub up: KeyCode,
+    pub left: KeyCode,
+    pub down: KeyCode,
+    pub right: KeyCode,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GamepadStick {
+    Left,
+    Right,
+}
+
+/// Bindings of a single player for every kind of device
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PlayerBindings {
+    /// Stick values under this length are ignored
+    pub deadzone: f32,
+    pub rotate_keys: DirectionKeys,
+    pub rotate_stick: GamepadStick,
+    pub keyboard: BTreeMap<Action, KeyCode>,
+    pub gamepad: BTreeMap<Action, GamepadButtonType>,
+    /// The mouse always aims toward the cursor
+    pub mouse: BTreeMap<Action, MouseButton>,
+}
+
+impl PlayerBindings {
+    /// Default bindings of the `index`-th player, so that several players can share a keyboard
+    fn default_for(index: usize) -> Self {
+        let (rotate_keys, buttons) = match index % 4 {
+            0 => (
+                [KeyCode::KeyW, KeyCode::KeyA, KeyCode::KeyS, KeyCode::KeyD],
+                [
+                    KeyCode::Space,
+                    KeyCode::ShiftLeft,
+                    KeyCode::KeyE,
+                    KeyCode::KeyQ,
+                    KeyCode::Escape,
+                ],
+            ),
+            1 => (
+                [
+                    KeyCode::ArrowUp,
+                    KeyCode::ArrowLeft,
+                    KeyCode::ArrowDown,
+                    KeyCode::ArrowRight,
+                ],
+                [
+                    KeyCode::ControlRight,
+                    KeyCode::ShiftRight,
+                    KeyCode::Enter,
+                    KeyCode::AltRight,
+                    KeyCode::Backspace,
+                ],
+            ),
+            2 => (
+                [KeyCode::KeyI, KeyCode::KeyJ, KeyCode::KeyK, KeyCode::KeyL],
+                [
+                    KeyCode::KeyN,
+                    KeyCode::KeyM,
+                    KeyCode::KeyU,
+                    KeyCode::KeyH,
+                    KeyCode::KeyO,
+                ],
+            ),
+            _ => (
+                [
+                    KeyCode::Numpad8,
+                    KeyCode::Numpad4,
+                    KeyCode::Numpad5,
+                    KeyCode::Numpad6,
+                ],
+                [
+                    KeyCode::Numpad0,
+                    KeyCode::NumpadDecimal,
+                    KeyCode::NumpadEnter,
+                    KeyCode::NumpadSubtract,
+                    KeyCode::NumpadAdd,
+                ],
+            ),
+        };
+        let [up, left, down, right] = rotate_keys;
+
+        Self {
+            deadzone: 0.1,
+            rotate_keys: DirectionKeys {
+                up,
+                left,
+                down,
+                right,
+            },
+            rotate_stick: GamepadStick::Left,
+            keyboard: Action::BUTTONS.into_iter().zip(buttons).collect(),
+            gamepad: Action::BUTTONS
+                .into_iter()
+                .zip([
+                    GamepadButtonType::South,
+                    GamepadButtonType::West,
+                    GamepadButtonType::RightTrigger,
+                    GamepadButtonType::North,
+                    GamepadButtonType::Start,
+                ])
+                .collect(),
+            mouse: [
+                (Action::Thrust, MouseButton::Left),
+                (Action::Brake, MouseButton::Right),
+                (Action::Boost, MouseButton::Middle),
+            ]
+            .into_iter()
+            .collect(),
+        }
+    }
+}
+
+/// Bindings of every player, saved to the config directory
+#[derive(Resource, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct InputBindings {
+    pub players: Vec<PlayerBindings>,
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        Self {
+            players: (0..MAX_PLAYERS as usize)
+                .map(PlayerBindings::default_for)
+                .collect(),
+        }
+    }
+}
+
+impl InputBindings {
+    fn path() -> PathBuf {
+        dirs::config_dir()
+            .unwrap_or_default()
+            .join("bevy_asteroid")
+            .join("bindings.ron")
+    }
+
+    /// Loads the bindings from the config file, falling back to the default ones
+    fn load() -> Self {
+        let path = Self::path();
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            return Self::default();
+        };
+
+        match ron::from_str::<InputBindings>(&content) {
+            Ok(mut bindings) => {
+                // Players missing from the file keep their default bindings
+                let defaults = Self::default();
+                bindings
+                    .players
+                    .extend(defaults.players.into_iter().skip(bindings.players.len()));
+                bindings
+            }
+            Err(err) => {
+                warn!("Invalid bindings file {}: {err}", path.display());
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save(&self) {
+        let path = Self::path();
+        let content = match ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()) {
+            Ok(content) => content,
+            Err(err) => {
+                error!("Could not serialize the bindings: {err}");
+                return;
+            }
+        };
+
+        if let Err(err) = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(&path, content))
+        {
+            error!("Could not save the bindings to {}: {err}", path.display());
+        }
+    }
+
+    pub fn player(&self, player_id: PlayerId) -> &PlayerBindings {
+        &self.players[player_id.0 as usize % self.players.len()]
+    }
+
+    /// Whether any player just pressed the button bound to [`Action::Pause`], on any device
+    pub fn pause_just_pressed(
+        &self,
+        keys: &ButtonInput<KeyCode>,
+        gamepad_buttons: &ButtonInput<GamepadButton>,
+        mouse_buttons: &ButtonInput<MouseButton>,
+    ) -> bool {
+        self.players.iter().any(|player_bindings| {
+            player_bindings
+                .keyboard
+                .get(&Action::Pause)
+                .is_some_and(|key| keys.just_pressed(*key))
+                || player_bindings
+                    .mouse
+                    .get(&Action::Pause)
+                    .is_some_and(|button| mouse_buttons.just_pressed(*button))
+                || player_bindings
+                    .gamepad
+                    .get(&Action::Pause)
+                    .is_some_and(|button_type| gamepad_just_pressed(gamepad_buttons, *button_type))
+        })
+    }
+}
+
+/// Whether a button of this type was just pressed on any gamepad
+pub fn gamepad_just_pressed(
+    buttons: &ButtonInput<GamepadButton>,
+    button_type: GamepadButtonType,
+) -> bool {
+    buttons
+        .get_just_pressed()
+        .any(|button| button.button_type == button_type)
+}
+
+/// Device controlling a player
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PlayerDevice {
+    #[default]
+    None,
+    Gamepad(Gamepad),
+    Keyboard,
+    /// Aim toward the cursor
+    Mouse,
+    /// Controlled by the game
+    Bot(BotDifficulty),
+}
+
+impl PlayerDevice {
+    /// Next device in the list of the devices a player can pick
+    pub fn next(self, gamepads: &Gamepads) -> Self {
+        let mut devices = vec![
+            PlayerDevice::None,
+            PlayerDevice::Keyboard,
+            PlayerDevice::Mouse,
+        ];
+        devices.extend(BotDifficulty::ALL.map(PlayerDevice::Bot));
+        devices.extend(gamepads.iter().map(PlayerDevice::Gamepad));
+
+        let index = devices.iter().position(|d| *d == self).unwrap_or(0);
+        devices[(index + 1) % devices.len()]
+    }
+}
+
+/// What a player wants to do this frame, whatever the device used
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct PlayerInput {
+    /// Direction the ship should face
+    pub aim: Option<Vec2>,
+    pub thrust: bool,
+    pub brake: bool,
+    pub boost: bool,
+    /// Held down, the link toggling when the button gets pressed
+    pub toggle_link: bool,
+    /// Only set on the frame the button is pressed
+    pub pause: bool,
+}
+
+impl PlayerInput {
+    fn from_buttons(aim: Option<Vec2>, pressed: impl Fn(Action) -> bool, pause: bool) -> Self {
+        Self {
+            aim,
+            thrust: pressed(Action::Thrust),
+            brake: pressed(Action::Brake),
+            boost: pressed(Action::Boost),
+            toggle_link: pressed(Action::ToggleLink),
+            pause,
+        }
+    }
+}
+
+fn gamepad_connect(
+    mut players: Query<&mut PlayerDevice>,
+    mut evr_gamepad: EventReader<GamepadEvent>,
+) {
+    for ev in evr_gamepad.read() {
+        let GamepadEvent::Connection(ev_conn) = ev else {
+            continue;
+        };
+        match &ev_conn.connection {
+            GamepadConnection::Connected(_info) => {
+                for mut player_device in players.iter_mut() {
+                    if *player_device == PlayerDevice::None {
+                        *player_device = PlayerDevice::Gamepad(ev_conn.gamepad);
+                        break;
+                    }
+                }
+            }
+            GamepadConnection::Disconnected => {
+                for mut player_device in players.iter_mut() {
+                    if *player_device == PlayerDevice::Gamepad(ev_conn.gamepad) {
+                        *player_device = PlayerDevice::None;
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn read_player_devices(
+    bindings: Res<InputBindings>,
+    axes: Res<Axis<GamepadAxis>>,
+    buttons: Res<ButtonInput<GamepadButton>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform)>,
+    mut players: Query<(&Transform, &PlayerId, &PlayerDevice, &mut PlayerInput)>,
+) {
+    for (player_transform, player_id, player_device, mut player_input) in players.iter_mut() {
+        let player_bindings = bindings.player(*player_id);
+
+        *player_input = match *player_device {
+            PlayerDevice::None => PlayerInput::default(),
+            // Bots decide their input during the fixed steps
+            PlayerDevice::Bot(_) => continue,
+            PlayerDevice::Gamepad(gamepad) => {
+                let (axis_x, axis_y) = match player_bindings.rotate_stick {
+                    GamepadStick::Left => {
+                        (GamepadAxisType::LeftStickX, GamepadAxisType::LeftStickY)
+                    }
+                    GamepadStick::Right => {
+                        (GamepadAxisType::RightStickX, GamepadAxisType::RightStickY)
+                    }
+                };
+                let axis = |axis_type| axes.get(GamepadAxis { gamepad, axis_type });
+
+                let aim = match (axis(axis_x), axis(axis_y)) {
+                    (Some(x), Some(y)) => Some(Vec2::new(x, y)),
+                    _ => None,
+                }
+                .filter(|stick| stick.length() > player_bindings.deadzone);
+
+                let button = |action| {
+                    player_bindings
+                        .gamepad
+                        .get(&action)
+                        .map(|&button_type| GamepadButton {
+                            gamepad,
+                            button_type,
+                        })
+                };
+
+                PlayerInput::from_buttons(
+                    aim,
+                    |action| button(action).is_some_and(|b| buttons.pressed(b)),
+                    button(Action::Pause).is_some_and(|b| buttons.just_pressed(b)),
+                )
+            }
+            PlayerDevice::Keyboard => {
+                let rotate_keys = player_bindings.rotate_keys;
+                let axis = |negative: KeyCode, positive: KeyCode| {
+                    keys.pressed(positive) as i8 as f32 - keys.pressed(negative) as i8 as f32
+                };
+                let direction = Vec2::new(
+                    axis(rotate_keys.left, rotate_keys.right),
+                    axis(rotate_keys.down, rotate_keys.up),
+                );
+
+                let key = |action| player_bindings.keyboard.get(&action);
+
+                PlayerInput::from_buttons(
+                    (direction != Vec2::ZERO).then_some(direction),
+                    |action| key(action).is_some_and(|k| keys.pressed(*k)),
+                    key(Action::Pause).is_some_and(|k| keys.just_pressed(*k)),
+                )
+            }
+            PlayerDevice::Mouse => {
+                let cursor = window_q
+                    .get_single()
+                    .ok()
+                    .and_then(|window| window.cursor_position());
+                let aim = cursor
+                    .zip(camera_q.get_single().ok())
+                    .and_then(|(cursor, (camera, camera_transform))| {
+                        camera.viewport_to_world_2d(camera_transform, cursor)
+                    })
+                    .map(|target| target - player_transform.translation.truncate())
+                    .filter(|direction| *direction != Vec2::ZERO);
+
+                let button = |action| player_bindings.mouse.get(&action);
+
+                PlayerInput::from_buttons(
+                    aim,
+                    |action| button(action).is_some_and(|b| mouse_buttons.pressed(*b)),
+                    button(Action::Pause).is_some_and(|b| mouse_buttons.just_pressed(*b)),
+                )
+            }
+        };
+    }
+}
+
+/// Systems filling the [`PlayerInput`] of every player from their devices
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ReadInputSet;
+
+/// Systems deciding the [`PlayerInput`] used by a fixed step, by recording or replaying it
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ApplyInputSet;
+
+pub struct PlayerInputPlugin;
+
+impl Plugin for PlayerInputPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(InputBindings::load())
+            .add_systems(Update, gamepad_connect)
+            .add_systems(
+                PreUpdate,
+                read_player_devices
+                    .in_set(ReadInputSet)
+                    .after(InputSystem)
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
@@ -0,0 +1,264 @@
+use avian2d::prelude::*;
+use bevy::prelude::*;
+use bevy_hanabi::prelude::*;
+use serde::Deserialize;
+
+use crate::asteroid::Asteroid;
+use crate::config::init_ron_resource;
+use crate::player::{PlayerId, Wreck};
+use crate::{explosion_effect, GameState, GameplaySet};
+
+/// Behavior and looks of a kind of asteroid, every field being optional in the data file
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct AsteroidKind {
+    /// Chance of a spawned asteroid to be of this kind, relative to the other kinds
+    pub weight: f32,
+    /// Color multiplying the asteroid texture, above 1 to glow
+    pub tint: [f32; 3],
+    /// Color and speed of the particles when the asteroid gets destroyed
+    pub effect_color: [f32; 3],
+    pub effect_speed: f32,
+    /// Link passes needed to destroy the asteroid
+    pub armor: u32,
+    /// Multiplies the score of the asteroid
+    pub score_factor: usize,
+    /// Ships within the radius get damaged when the asteroid gets destroyed
+    pub explosion_radius: f32,
+    pub explosion_damage: f32,
+    /// How fast the asteroid turns toward the closest ship, in radians per second
+    pub homing: f32,
+    /// Seconds during which the ships of a link touched by the asteroid are slowed down
+    pub chill_duration: f32,
+}
+
+impl Default for AsteroidKind {
+    fn default() -> Self {
+        Self {
+            weight: 1.0,
+            tint: [1.0, 1.0, 1.0],
+            effect_color: [0.6, 0.5, 0.55],
+            effect_speed: 400.0,
+            armor: 1,
+            score_factor: 1,
+            explosion_radius: 0.0,
+            explosion_damage: 0.0,
+            homing: 0.0,
+            chill_duration: 0.0,
+        }
+    }
+}
+
+impl AsteroidKind {
+    pub fn color(&self) -> Color {
+        let [r, g, b] = self.tint;
+        Color::srgb(r, g, b)
+    }
+}
+
+/// Kinds of asteroids, loaded from `assets/config/game.asteroids.ron`
+#[derive(Asset, Resource, TypePath, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct AsteroidKinds {
+    pub kinds: Vec<AsteroidKind>,
+}
+
+impl Default for AsteroidKinds {
+    fn default() -> Self {
+        Self {
+            kinds: vec![
+                AsteroidKind {
+                    weight: 6.0,
+                    ..default()
+                },
+                AsteroidKind {
+                    tint: [0.6, 0.65, 0.8],
+                    effect_color: [0.5, 0.55, 0.8],
+                    armor: 3,
+                    score_factor: 3,
+                    ..default()
+                },
+                AsteroidKind {
+                    tint: [2.0, 0.7, 0.3],
+                    effect_color: [3.0, 1.0, 0.2],
+                    effect_speed: 700.0,
+                    score_factor: 2,
+                    explosion_radius: 90.0,
+                    explosion_damage: 0.15,
+                    ..default()
+                },
+                AsteroidKind {
+                    tint: [1.0, 0.4, 1.2],
+                    effect_color: [1.2, 0.4, 1.5],
+                    score_factor: 2,
+                    homing: 0.6,
+                    ..default()
+                },
+                AsteroidKind {
+                    tint: [0.6, 1.2, 1.6],
+                    effect_color: [0.7, 1.5, 2.0],
+                    chill_duration: 2.0,
+                    ..default()
+                },
+            ],
+        }
+    }
+}
+
+impl AsteroidKinds {
+    /// Kind of asteroid matching a random number between 0 and 1, following the weights
+    pub fn pick(&self, random_01: f32) -> usize {
+        let total = self
+            .kinds
+            .iter()
+            .map(|kind| kind.weight.max(0.0))
+            .sum::<f32>();
+        let mut roll = random_01 * total;
+        for (index, kind) in self.kinds.iter().enumerate() {
+            roll -= kind.weight.max(0.0);
+            if roll < 0.0 {
+                return index;
+            }
+        }
+        self.kinds.len().saturating_sub(1)
+    }
+
+    /// The default kind stands in for kinds removed from the data file while the game is running
+    pub fn get(&self, kind: usize) -> AsteroidKind {
+        self.kinds.get(kind).cloned().unwrap_or_default()
+    }
+}
+
+/// Particle effect of every kind, in the order of `AsteroidKinds`
+#[derive(Resource, Default)]
+pub struct KindEffects(Vec<Handle<EffectAsset>>);
+
+impl KindEffects {
+    pub fn get(&self, kind: usize) -> Option<Handle<EffectAsset>> {
+        self.0.get(kind).cloned()
+    }
+}
+
+fn build_kind_effects(
+    kinds: Res<AsteroidKinds>,
+    mut kind_effects: ResMut<KindEffects>,
+    mut effects: ResMut<Assets<EffectAsset>>,
+) {
+    kind_effects.0 = kinds
+        .kinds
+        .iter()
+        .map(|kind| {
+            let [r, g, b] = kind.effect_color;
+            explosion_effect(&mut effects, Vec4::new(r, g, b, 1.0), kind.effect_speed)
+        })
+        .collect();
+}
+
+/// Ship slowed down by an ice asteroid
+#[derive(Component)]
+pub struct Chilled(pub Timer);
+
+/// Acceleration factor of chilled ships
+pub const CHILL_FACTOR: f32 = 0.5;
+
+fn tick_chilled(mut commands: Commands, mut ships: Query<(Entity, &mut Chilled)>, time: Res<Time>) {
+    for (ship, mut chilled) in ships.iter_mut() {
+        if chilled.0.tick(time.delta()).finished() {
+            commands.entity(ship).remove::<Chilled>();
+        }
+    }
+}
+
+/// Homing asteroids turn toward the closest ship, keeping their speed
+fn home_asteroids(
+    mut asteroids: Query<(&Transform, &mut LinearVelocity, &Asteroid)>,
+    ships: Query<&Transform, (With<PlayerId>, Without<Wreck>, Without<Asteroid>)>,
+    kinds: Res<AsteroidKinds>,
+    time: Res<Time>,
+) {
+    for (asteroid_trans, mut velocity, asteroid) in asteroids.iter_mut() {
+        let homing = kinds.get(asteroid.kind()).homing;
+        if homing <= 0.0 || velocity.0 == Vec2::ZERO {
+            continue;
+        }
+        let position = asteroid_trans.translation.truncate();
+        let Some(target) = ships
+            .iter()
+            .map(|ship_trans| ship_trans.translation.truncate())
+            .min_by(|a, b| {
+                a.distance_squared(position)
+                    .total_cmp(&b.distance_squared(position))
+            })
+        else {
+            continue;
+        };
+        let Some(toward) = (target - position).try_normalize() else {
+            continue;
+        };
+        let max_turn = homing * time.delta_seconds();
+        let turn = velocity.0.angle_between(toward).clamp(-max_turn, max_turn);
+        velocity.0 = Vec2::from_angle(turn).rotate(velocity.0);
+    }
+}
+
+pub struct AsteroidKindPlugin;
+
+impl Plugin for AsteroidKindPlugin {
+    fn build(&self, app: &mut App) {
+        init_ron_resource::<AsteroidKinds>(app, "config/game.asteroids.ron", "asteroids.ron");
+        app.init_resource::<KindEffects>()
+            .add_systems(
+                FixedUpdate,
+                (
+                    home_asteroids.in_set(GameplaySet::Link),
+                    tick_chilled.in_set(GameplaySet::Motion),
+                )
+                    .run_if(in_state(GameState::InGame)),
+            )
+            .add_systems(
+                Update,
+                build_kind_effects.run_if(resource_changed::<AsteroidKinds>),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(weights: &[f32]) -> AsteroidKinds {
+        AsteroidKinds {
+            kinds: weights
+                .iter()
+                .map(|&weight| AsteroidKind {
+                    weight,
+                    ..default()
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn pick_follows_weights() {
+        let kinds = kinds(&[3.0, 1.0]);
+        assert_eq!(kinds.pick(0.0), 0);
+        assert_eq!(kinds.pick(0.74), 0);
+        assert_eq!(kinds.pick(0.76), 1);
+        assert_eq!(kinds.pick(1.0), 1);
+    }
+
+    #[test]
+    fn pick_skips_kinds_without_weight() {
+        let kinds = kinds(&[0.0, -2.0, 1.0]);
+        assert_eq!(kinds.pick(0.0), 2);
+        assert_eq!(kinds.pick(0.5), 2);
+    }
+
+    #[test]
+    fn pick_without_kinds() {
+        assert_eq!(kinds(&[]).pick(0.5), 0);
+        assert_eq!(kinds(&[0.0, 0.0]).pick(0.5), 1);
+        // Picking the default kind for a missing one
+        assert_eq!(kinds(&[]).get(0).armor, AsteroidKind::default().armor);
+    }
+}
@@ -1,35 +1,282 @@
-#![feature(array_windows)]
 #![allow(clippy::complexity)]
 
+mod arena;
 mod asteroid;
+mod bot;
+mod config;
+mod energy;
+mod headless;
+mod health;
+mod highscore;
+mod input;
+mod kind;
+mod menu;
 mod player;
+mod powerup;
+mod remap;
+mod replay;
+mod wave;
 
-use asteroid::{AsteroidPlugin, HealthParent};
+use arena::{ArenaPlugin, ARENA_SIZE};
+use asteroid::AsteroidPlugin;
 use avian2d::prelude::*;
 use bevy::core_pipeline::bloom::BloomSettings;
+use bevy::ecs::schedule::ScheduleLabel;
 use bevy::prelude::*;
 use bevy_hanabi::prelude::*;
 use bevy_prng::WyRand;
 use bevy_rand::plugin::EntropyPlugin;
+use bevy_rand::resource::GlobalEntropy;
+use bot::BotPlugin;
+use config::ConfigPlugin;
+use energy::LinkEnergyPlugin;
+use health::HealthPlugin;
+use highscore::HighScorePlugin;
+use input::{ApplyInputSet, PlayerInputPlugin};
+use kind::AsteroidKindPlugin;
+use menu::MenuPlugin;
 use player::PlayerPlugin;
+use powerup::PowerUpPlugin;
+use remap::RemapPlugin;
+use replay::{Playback, ReplayPlugin};
+use wave::{WavePlugin, WaveText};
 
 fn main() {
-    App::new()
-        .add_plugins((DefaultPlugins, PhysicsPlugins::default(), HanabiPlugin))
-        // .add_plugins(PhysicsDebugPlugin::default())
-        .insert_state(GameState::InGame)
+    let cli_seed = parse_cli_arg::<u64>("--seed", "an unsigned integer");
+
+    if cli_flag("--headless") {
+        headless::run(cli_seed.unwrap_or_else(random_seed));
+        return;
+    }
+
+    let mut app = App::new();
+    app.add_plugins((
+        DefaultPlugins,
+        PhysicsPlugins::new(FixedPostUpdate),
+        HanabiPlugin,
+    ));
+    // .add_plugins(PhysicsDebugPlugin::default())
+    #[cfg(feature = "client")]
+    app.add_systems(Startup, setup_sound);
+    add_gameplay(&mut app)
+        .add_plugins(MenuPlugin)
+        .add_plugins(RemapPlugin)
+        .add_plugins(ReplayPlugin)
+        .add_plugins(HighScorePlugin)
+        .insert_resource(RunSeed(cli_seed.unwrap_or_else(random_seed)))
+        .insert_resource(CliSeed(cli_seed))
+        .add_systems(Startup, setup_map)
+        // .add_systems(Update, on_resize)
+        .run();
+}
+
+/// Everything making a game, shared with the headless simulation so nothing here needs a window
+fn add_gameplay(app: &mut App) -> &mut App {
+    app.insert_state(GameState::MainMenu)
+        .init_schedule(StartRun)
+        .add_plugins(ConfigPlugin)
         .add_plugins(PlayerPlugin)
+        .add_plugins(PlayerInputPlugin)
+        .add_plugins(BotPlugin)
         .add_plugins(AsteroidPlugin)
+        .add_plugins(AsteroidKindPlugin)
+        .add_plugins(HealthPlugin)
+        .add_plugins(WavePlugin)
+        .add_plugins(PowerUpPlugin)
+        .add_plugins(LinkEnergyPlugin)
+        .add_plugins(ArenaPlugin)
         .add_plugins(EntropyPlugin::<WyRand>::default())
         .insert_resource(Gravity(Vec2::ZERO))
-        .add_systems(Startup, setup_map)
-        .add_systems(Startup, setup_score_ui)
+        .insert_resource(Time::<Fixed>::from_hz(FIXED_HZ))
+        .insert_resource(Time::new_with(Physics::fixed_once_hz(FIXED_HZ)))
+        .configure_sets(
+            FixedUpdate,
+            (
+                GameplaySet::Controls,
+                GameplaySet::Motion,
+                GameplaySet::Bounds,
+                GameplaySet::Link,
+                GameplaySet::Waves,
+                GameplaySet::Spawn,
+                GameplaySet::Hits,
+                GameplaySet::PowerUps,
+                GameplaySet::Energy,
+                GameplaySet::Wrecks,
+                GameplaySet::Revive,
+            )
+                .chain()
+                .after(ApplyInputSet),
+        )
         .add_systems(Startup, setup_effects)
-        .add_systems(Startup, setup_sound)
+        .add_systems(StartRun, setup_score_ui)
+        .add_systems(
+            OnTransition {
+                exited: GameState::MainMenu,
+                entered: GameState::InGame,
+            },
+            (new_run_seed, start_run).chain(),
+        )
+        .add_systems(
+            OnTransition {
+                exited: GameState::GameOver,
+                entered: GameState::InGame,
+            },
+            start_run,
+        )
+        .add_systems(OnExit(GameState::GameOver), despawn_run)
+        .add_systems(
+            OnTransition {
+                exited: GameState::Paused,
+                entered: GameState::MainMenu,
+            },
+            despawn_run,
+        )
+        .add_systems(OnEnter(GameState::Paused), pause_time)
+        .add_systems(OnExit(GameState::Paused), unpause_time)
+        .add_systems(OnEnter(GameState::GameOver), pause_time)
+        .add_systems(OnExit(GameState::GameOver), unpause_time)
         .add_systems(Update, update_time_ui)
-        // .add_systems(Update, on_resize)
-        .add_systems(OnEnter(GameState::EndGame), end_game)
-        .run();
+}
+
+/// Schedule running the setup of a new game, from the main menu or after a game over
+#[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StartRun;
+
+/// Stages of a fixed step, always run in this order after the inputs so that a replay plays the
+/// recorded game again exactly
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GameplaySet {
+    /// The players steer their ships
+    Controls,
+    /// Everything else moves and the timers tick
+    Motion,
+    /// The arena keeps the ships in and wraps or removes the asteroids
+    Bounds,
+    /// The links follow the ships
+    Link,
+    /// The waves arm the spawners...
+    Waves,
+    /// ...which fire
+    Spawn,
+    /// Asteroids and spawners get hit
+    Hits,
+    PowerUps,
+    Energy,
+    /// Ships out of health become wrecks...
+    Wrecks,
+    /// ...that their partners can revive
+    Revive,
+}
+
+/// Rate of the fixed steps running the gameplay, so that a game only depends on its inputs
+pub const FIXED_HZ: f64 = 64.0;
+
+/// Marker for every entity belonging to a game, despawned when leaving it
+#[derive(Component)]
+pub struct RunEntity;
+
+/// Seed of the random number generators of the current game
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct RunSeed(pub u64);
+
+/// Seed given with `--seed`, used by every game started from the main menu
+#[derive(Resource)]
+struct CliSeed(Option<u64>);
+
+/// Value of the command line option `name`, given as `name value` or `name=value`
+///
+/// The value is empty when the option is given without one.
+pub fn cli_arg(name: &str) -> Option<String> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.strip_prefix(name) {
+            Some("") => return Some(args.next().unwrap_or_default()),
+            Some(value) => {
+                if let Some(value) = value.strip_prefix('=') {
+                    return Some(value.to_string());
+                }
+            }
+            None => {}
+        }
+    }
+    None
+}
+
+/// Whether the command line flag `name` is given
+pub fn cli_flag(name: &str) -> bool {
+    std::env::args().skip(1).any(|arg| arg == name)
+}
+
+/// Parses the value of the command line option `name`, exiting when it is not `expected`
+pub fn parse_cli_arg<T: std::str::FromStr>(name: &str, expected: &str) -> Option<T> {
+    let value = cli_arg(name)?;
+    match value.parse() {
+        Ok(value) => Some(value),
+        Err(_) => {
+            eprintln!("{name} expects {expected}");
+            std::process::exit(2);
+        }
+    }
+}
+
+pub fn random_seed() -> u64 {
+    use std::hash::{BuildHasher, Hasher};
+
+    // The std hasher keys are randomly generated by the OS
+    std::collections::hash_map::RandomState::new()
+        .build_hasher()
+        .finish()
+}
+
+fn new_run_seed(
+    mut run_seed: ResMut<RunSeed>,
+    cli_seed: Res<CliSeed>,
+    playback: Option<Res<Playback>>,
+) {
+    // A replay is played with the seed it was recorded with
+    if playback.is_some() {
+        return;
+    }
+    run_seed.0 = cli_seed.0.unwrap_or_else(random_seed);
+}
+
+fn start_run(world: &mut World) {
+    // Everything random in a game derives from its seed so that it can be replayed
+    let seed = world.resource::<RunSeed>().0;
+    world
+        .resource_mut::<GlobalEntropy<WyRand>>()
+        .reseed(seed.to_le_bytes());
+    world.run_schedule(StartRun);
+}
+
+/// Starts the current game over without going through the menus
+pub fn restart_run(world: &mut World) {
+    let run_entities = world
+        .query_filtered::<Entity, With<RunEntity>>()
+        .iter(world)
+        .collect::<Vec<_>>();
+    for entity in run_entities {
+        if let Some(entity) = world.get_entity_mut(entity) {
+            entity.despawn_recursive();
+        }
+    }
+    start_run(world);
+}
+
+fn despawn_run(mut commands: Commands, run_q: Query<Entity, With<RunEntity>>) {
+    for e in run_q.iter() {
+        commands.entity(e).despawn_recursive();
+    }
+}
+
+fn pause_time(mut time1: ResMut<Time<Physics>>, mut time2: ResMut<Time<Virtual>>) {
+    time1.pause();
+    time2.pause();
+}
+
+fn unpause_time(mut time1: ResMut<Time<Physics>>, mut time2: ResMut<Time<Virtual>>) {
+    time1.unpause();
+    time2.unpause();
 }
 
 #[derive(Component)]
@@ -55,10 +302,7 @@ fn setup_map(mut commands: Commands, asset_server: Res<AssetServer>) {
     commands.spawn((
         SpriteBundle {
             sprite: Sprite {
-                custom_size: Some(Vec2 {
-                    x: 1920.0,
-                    y: 1080.0,
-                }),
+                custom_size: Some(ARENA_SIZE),
                 ..default()
             },
             transform: Transform::from_xyz(0.0, 0.0, -100.0),
@@ -79,37 +323,7 @@ fn setup_map(mut commands: Commands, asset_server: Res<AssetServer>) {
 //     }
 // }
 
-fn end_game(
-    mut commands: Commands,
-    health_q: Query<Entity, With<HealthParent>>,
-    mut time1: ResMut<Time<Physics>>,
-    mut time2: ResMut<Time<Virtual>>,
-) {
-    for e in health_q.iter() {
-        commands.entity(e).despawn_recursive();
-    }
-    commands.spawn(TextBundle {
-        style: Style {
-            align_self: AlignSelf::Center,
-            justify_self: JustifySelf::Center,
-            ..default()
-        },
-        text: Text::from_section(
-            "GAME END",
-            TextStyle {
-                font_size: 140.0,
-                color: Color::srgba(0.9, 0.2, 0.3, 1.0),
-                ..default()
-            },
-        )
-        .with_justify(JustifyText::Center),
-        ..default()
-    });
-
-    time1.pause();
-    time2.pause();
-}
-
+#[cfg(feature = "client")]
 fn setup_sound(asset_server: Res<AssetServer>, mut commands: Commands) {
     commands.spawn(AudioBundle {
         source: asset_server.load("music/BossTheme.ogg"),
@@ -121,26 +335,47 @@ fn setup_sound(asset_server: Res<AssetServer>, mut commands: Commands) {
 struct ScoreText(usize);
 
 fn setup_score_ui(mut commands: Commands) {
-    commands.spawn((
-        // Create a TextBundle that has a Text with a single section.
-        TextBundle::from_section(
-            // Accepts a `String` or any type that converts into a `String`, such as `&str`
-            "00",
-            TextStyle {
-                // This font is loaded and will be used instead of the default font.
-                font_size: 60.0,
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    align_self: AlignSelf::Start,
+                    justify_self: JustifySelf::End,
+                    align_items: AlignItems::Center,
+                    column_gap: Val::Px(40.0),
+                    ..default()
+                },
                 ..default()
             },
-        ) // Set the justification of the Text
-        .with_text_justify(JustifyText::Center)
-        // Set the style of the TextBundle itself.
-        .with_style(Style {
-            align_self: AlignSelf::Start,
-            justify_self: JustifySelf::End,
-            ..default()
-        }),
-        ScoreText(0),
-    ));
+            RunEntity,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    "GET READY",
+                    TextStyle {
+                        font_size: 40.0,
+                        color: Color::srgba(0.7, 0.7, 0.7, 1.0),
+                        ..default()
+                    },
+                ),
+                WaveText,
+            ));
+            parent.spawn((
+                // Create a TextBundle that has a Text with a single section.
+                TextBundle::from_section(
+                    // Accepts a `String` or any type that converts into a `String`, such as `&str`
+                    "00",
+                    TextStyle {
+                        // This font is loaded and will be used instead of the default font.
+                        font_size: 60.0,
+                        ..default()
+                    },
+                ) // Set the justification of the Text
+                .with_text_justify(JustifyText::Center),
+                ScoreText(0),
+            ));
+        });
 }
 
 fn update_time_ui(mut text_q: Query<(&mut Text, &ScoreText)>) {
@@ -151,17 +386,30 @@ fn update_time_ui(mut text_q: Query<(&mut Text, &ScoreText)>) {
 
 #[derive(States, Debug, Clone, PartialEq, Eq, Hash)]
 enum GameState {
+    MainMenu,
+    Bindings,
     InGame,
-    EndGame,
+    Paused,
+    GameOver,
 }
 
 #[derive(Resource)]
 struct AsteroidEffect(Handle<EffectAsset>);
 
 fn setup_effects(mut commands: Commands, mut effects: ResMut<Assets<EffectAsset>>) {
-    // Define a color gradient from red to transparent black
+    let effect_handle = explosion_effect(&mut effects, Vec4::new(0.6, 0.5, 0.55, 1.), 400.);
+    commands.insert_resource(AsteroidEffect(effect_handle));
+}
+
+/// Burst of particles fading from `color` to transparent, flying away at `speed`
+fn explosion_effect(
+    effects: &mut Assets<EffectAsset>,
+    color: Vec4,
+    speed: f32,
+) -> Handle<EffectAsset> {
+    // Define a color gradient from the given color to transparent black
     let mut gradient = Gradient::new();
-    gradient.add_key(0.0, Vec4::new(0.6, 0.5, 0.55, 1.));
+    gradient.add_key(0.0, color);
     gradient.add_key(1.0, Vec4::splat(0.));
 
     // Create a new expression module
@@ -179,7 +427,7 @@ fn setup_effects(mut commands: Commands, mut effects: ResMut<Assets<EffectAsset>
     // away from the (same) sphere center.
     let init_vel = SetVelocitySphereModifier {
         center: module.lit(Vec3::ZERO),
-        speed: module.lit(400.),
+        speed: module.lit(speed),
     };
 
     // Initialize the total lifetime of the particle, that is
@@ -212,7 +460,5 @@ fn setup_effects(mut commands: Commands, mut effects: ResMut<Assets<EffectAsset>
     .render(ColorOverLifetimeModifier { gradient });
 
     // Insert into the asset system
-    let effect_handle = effects.add(effect);
-
-    commands.insert_resource(AsteroidEffect(effect_handle));
+    effects.add(effect)
 }
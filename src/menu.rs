@@ -0,0 +1,456 @@
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::prelude::*;
+
+use crate::arena::ArenaBounds;
+use crate::highscore::{HighScoreEntry, HighScores, SurvivalTime};
+use crate::input::{gamepad_just_pressed, InputBindings, PlayerDevice, PlayerInput};
+use crate::player::{PlayersConfig, MAX_PLAYERS, MIN_PLAYERS};
+use crate::replay::{stop_playback, Playback};
+use crate::{random_seed, GameState, RunSeed, ScoreText};
+
+#[derive(Component)]
+struct MenuOptionsText;
+
+/// Seed of the next game, editable on the game over screen
+#[derive(Component, Default)]
+struct SeedField {
+    /// Whether digits were typed yet, the first one replacing the seed of the last game
+    edited: bool,
+}
+
+#[derive(Component)]
+struct HighScoreText;
+
+#[derive(Component)]
+struct NameEntryText;
+
+/// High score waiting for the name of the players, the other game over inputs being ignored
+#[derive(Resource)]
+struct NameEntry(HighScoreEntry);
+
+const MAX_NAME_LENGTH: usize = 12;
+
+fn spawn_screen(commands: &mut Commands, state: GameState, title: &str, hint: &str, color: Color) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    row_gap: Val::Px(30.0),
+                    ..default()
+                },
+                ..default()
+            },
+            StateScoped(state),
+        ))
+        .with_children(|parent| {
+            parent.spawn(
+                TextBundle::from_section(
+                    title,
+                    TextStyle {
+                        font_size: 140.0,
+                        color,
+                        ..default()
+                    },
+                )
+                .with_text_justify(JustifyText::Center),
+            );
+            parent.spawn(
+                TextBundle::from_section(
+                    hint,
+                    TextStyle {
+                        font_size: 40.0,
+                        color: Color::srgba(0.9, 0.9, 0.9, 1.0),
+                        ..default()
+                    },
+                )
+                .with_text_justify(JustifyText::Center),
+            );
+        });
+}
+
+fn setup_main_menu(mut commands: Commands) {
+    spawn_screen(
+        &mut commands,
+        GameState::MainMenu,
+        "BEVY ASTEROID",
+        "Press Enter or Start to play, B to change the bindings, V to watch the last game",
+        Color::srgba(0.3, 0.8, 0.4, 1.0),
+    );
+
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 30.0,
+                color: Color::srgba(0.7, 0.7, 0.7, 1.0),
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(40.0),
+            bottom: Val::Px(40.0),
+            ..default()
+        }),
+        MenuOptionsText,
+        StateScoped(GameState::MainMenu),
+    ));
+}
+
+fn menu_options_input(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut bounds: ResMut<ArenaBounds>,
+    mut players_config: ResMut<PlayersConfig>,
+    gamepads: Res<Gamepads>,
+) {
+    const PLAYER_KEYS: [KeyCode; MAX_PLAYERS as usize] = [
+        KeyCode::Digit1,
+        KeyCode::Digit2,
+        KeyCode::Digit3,
+        KeyCode::Digit4,
+        KeyCode::Digit5,
+        KeyCode::Digit6,
+        KeyCode::Digit7,
+        KeyCode::Digit8,
+    ];
+    for (key, device) in PLAYER_KEYS.iter().zip(players_config.devices.iter_mut()) {
+        if keys.just_pressed(*key) {
+            *device = device.next(&gamepads);
+        }
+    }
+
+    if keys.just_pressed(KeyCode::ArrowLeft) && players_config.number_players > MIN_PLAYERS {
+        players_config.number_players -= 1;
+    }
+    if keys.just_pressed(KeyCode::ArrowRight) && players_config.number_players < MAX_PLAYERS {
+        players_config.number_players += 1;
+    }
+    if keys.just_pressed(KeyCode::KeyT) {
+        players_config.topology = players_config.topology.next();
+    }
+    if keys.just_pressed(KeyCode::KeyA) {
+        bounds.asteroids = bounds.asteroids.toggle();
+    }
+    if keys.just_pressed(KeyCode::KeyS) {
+        bounds.players = bounds.players.toggle();
+    }
+}
+
+fn update_menu_options_text(
+    bounds: Res<ArenaBounds>,
+    players_config: Res<PlayersConfig>,
+    mut text_q: Query<&mut Text, With<MenuOptionsText>>,
+) {
+    let devices = players_config.devices[..players_config.number_players as usize]
+        .iter()
+        .enumerate()
+        .map(|(i, device)| match device {
+            PlayerDevice::None => format!("[{}] Player {}: Auto\n", i + 1, i + 1),
+            PlayerDevice::Gamepad(gamepad) => {
+                format!("[{}] Player {}: Gamepad {}\n", i + 1, i + 1, gamepad.id)
+            }
+            device => format!("[{}] Player {}: {:?}\n", i + 1, i + 1, device),
+        })
+        .collect::<String>();
+
+    for mut text in text_q.iter_mut() {
+        text.sections[0].value = format!(
+            "[Left/Right] Players: {}\n{}[T] Link topology: {:?}\n\
+            [A] Asteroids leaving the arena: {:?}\n[S] Ships at the edge of the arena: {:?}",
+            players_config.number_players,
+            devices,
+            players_config.topology,
+            bounds.asteroids,
+            bounds.players
+        );
+    }
+}
+
+fn setup_pause_menu(mut commands: Commands) {
+    spawn_screen(
+        &mut commands,
+        GameState::Paused,
+        "PAUSED",
+        "Pause button to resume, Q or Select to quit to the main menu",
+        Color::srgba(0.9, 0.9, 0.9, 1.0),
+    );
+}
+
+fn setup_game_over(
+    mut commands: Commands,
+    run_seed: Res<RunSeed>,
+    high_scores: Res<HighScores>,
+    survival_time: Res<SurvivalTime>,
+    players_config: Res<PlayersConfig>,
+    playback: Option<Res<Playback>>,
+    score_q: Query<&ScoreText>,
+) {
+    spawn_screen(
+        &mut commands,
+        GameState::GameOver,
+        "GAME OVER",
+        "Enter or Start to replay the seed, N for a new seed, Escape or Select to go back to the main menu",
+        Color::srgba(0.9, 0.2, 0.3, 1.0),
+    );
+
+    let text_style = TextStyle {
+        font_size: 30.0,
+        color: Color::srgba(0.7, 0.7, 0.7, 1.0),
+        ..default()
+    };
+
+    commands.spawn((
+        TextBundle::from_section(format!("Seed: {}", run_seed.0), text_style.clone()).with_style(
+            Style {
+                position_type: PositionType::Absolute,
+                left: Val::Px(40.0),
+                bottom: Val::Px(40.0),
+                ..default()
+            },
+        ),
+        SeedField::default(),
+        StateScoped(GameState::GameOver),
+    ));
+
+    commands.spawn((
+        TextBundle::from_section(high_scores.table(None), text_style.clone()).with_style(Style {
+            position_type: PositionType::Absolute,
+            right: Val::Px(40.0),
+            bottom: Val::Px(40.0),
+            ..default()
+        }),
+        HighScoreText,
+        StateScoped(GameState::GameOver),
+    ));
+
+    // Watching a replay doesn't make a new score
+    let score = score_q.iter().map(|score| score.0).sum();
+    if playback.is_some() || !high_scores.qualifies(score) {
+        return;
+    }
+
+    commands.insert_resource(NameEntry(HighScoreEntry::now(
+        score,
+        run_seed.0,
+        survival_time.0,
+        players_config.number_players,
+    )));
+    commands.spawn((
+        TextBundle::from_section(name_entry_prompt(""), text_style).with_style(Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(40.0),
+            top: Val::Px(140.0),
+            ..default()
+        }),
+        NameEntryText,
+        StateScoped(GameState::GameOver),
+    ));
+}
+
+fn name_entry_prompt(name: &str) -> String {
+    format!("NEW HIGH SCORE!\nName: {name}_\n[Enter] Save")
+}
+
+fn name_entry_input(
+    mut commands: Commands,
+    mut keyboard_events: EventReader<KeyboardInput>,
+    buttons: Res<ButtonInput<GamepadButton>>,
+    mut name_entry: ResMut<NameEntry>,
+    mut high_scores: ResMut<HighScores>,
+    mut prompt_q: Query<(Entity, &mut Text), With<NameEntryText>>,
+    mut table_q: Query<&mut Text, (With<HighScoreText>, Without<NameEntryText>)>,
+) {
+    // Players on a gamepad can't type, they save with an anonymous name
+    let mut confirm = gamepad_just_pressed(&buttons, GamepadButtonType::South)
+        || gamepad_just_pressed(&buttons, GamepadButtonType::Start);
+
+    let name = &mut name_entry.0.name;
+    for event in keyboard_events.read() {
+        if !event.state.is_pressed() {
+            continue;
+        }
+        match &event.logical_key {
+            Key::Character(characters) => {
+                for character in characters.chars() {
+                    if (character.is_alphanumeric() || "-_.".contains(character))
+                        && name.chars().count() < MAX_NAME_LENGTH
+                    {
+                        name.push(character);
+                    }
+                }
+            }
+            Key::Space if !name.is_empty() && name.chars().count() < MAX_NAME_LENGTH => {
+                name.push(' ');
+            }
+            Key::Backspace => {
+                name.pop();
+            }
+            Key::Enter => confirm = true,
+            _ => {}
+        }
+    }
+
+    if !confirm {
+        for (_, mut text) in prompt_q.iter_mut() {
+            text.sections[0].value = name_entry_prompt(name);
+        }
+        return;
+    }
+
+    let mut entry = name_entry.0.clone();
+    entry.name = entry.name.trim().to_string();
+    if entry.name.is_empty() {
+        entry.name = "Anonymous".to_string();
+    }
+    let rank = high_scores.insert(entry);
+    high_scores.save();
+
+    for mut text in table_q.iter_mut() {
+        text.sections[0].value = high_scores.table(Some(rank));
+    }
+    for (prompt, _) in prompt_q.iter() {
+        commands.entity(prompt).despawn_recursive();
+    }
+    commands.remove_resource::<NameEntry>();
+}
+
+/// Typing digits changes the seed of the next game, N picks a random one
+fn seed_field_input(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut run_seed: ResMut<RunSeed>,
+    mut field_q: Query<(&mut Text, &mut SeedField)>,
+) {
+    let Ok((mut text, mut field)) = field_q.get_single_mut() else {
+        return;
+    };
+
+    const DIGITS: [(KeyCode, KeyCode); 10] = [
+        (KeyCode::Digit0, KeyCode::Numpad0),
+        (KeyCode::Digit1, KeyCode::Numpad1),
+        (KeyCode::Digit2, KeyCode::Numpad2),
+        (KeyCode::Digit3, KeyCode::Numpad3),
+        (KeyCode::Digit4, KeyCode::Numpad4),
+        (KeyCode::Digit5, KeyCode::Numpad5),
+        (KeyCode::Digit6, KeyCode::Numpad6),
+        (KeyCode::Digit7, KeyCode::Numpad7),
+        (KeyCode::Digit8, KeyCode::Numpad8),
+        (KeyCode::Digit9, KeyCode::Numpad9),
+    ];
+
+    let mut seed = run_seed.0;
+    for (digit, (key, numpad_key)) in DIGITS.iter().enumerate() {
+        if keys.any_just_pressed([*key, *numpad_key]) {
+            if !field.edited {
+                field.edited = true;
+                seed = 0;
+            }
+            seed = seed
+                .checked_mul(10)
+                .and_then(|seed| seed.checked_add(digit as u64))
+                .unwrap_or(seed);
+        }
+    }
+    if keys.just_pressed(KeyCode::Backspace) {
+        seed /= 10;
+    }
+    if keys.just_pressed(KeyCode::KeyN) {
+        seed = random_seed();
+    }
+
+    if seed != run_seed.0 {
+        run_seed.0 = seed;
+        text.sections[0].value = format!("Seed: {seed}");
+    }
+}
+
+fn menu_input(
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    buttons: Res<ButtonInput<GamepadButton>>,
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    bindings: Res<InputBindings>,
+) {
+    let start = gamepad_just_pressed(&buttons, GamepadButtonType::Start);
+    let select = gamepad_just_pressed(&buttons, GamepadButtonType::Select);
+
+    match state.get() {
+        GameState::MainMenu => {
+            if keys.just_pressed(KeyCode::Enter) || start {
+                next_state.set(GameState::InGame);
+            } else if keys.just_pressed(KeyCode::KeyB) {
+                next_state.set(GameState::Bindings);
+            }
+        }
+        // Leaving the bindings screen is handled by the screen itself
+        GameState::Bindings => {}
+        // Pausing is done with the pause action of the players
+        GameState::InGame => {}
+        GameState::Paused => {
+            if bindings.pause_just_pressed(&keys, &buttons, &mouse_buttons) {
+                next_state.set(GameState::InGame);
+            } else if keys.just_pressed(KeyCode::KeyQ) || select {
+                next_state.set(GameState::MainMenu);
+            }
+        }
+        GameState::GameOver => {
+            if keys.any_just_pressed([KeyCode::Enter, KeyCode::KeyR]) || start {
+                next_state.set(GameState::InGame);
+            } else if keys.just_pressed(KeyCode::Escape) || select {
+                next_state.set(GameState::MainMenu);
+            }
+        }
+    }
+}
+
+fn remove_name_entry(mut commands: Commands) {
+    commands.remove_resource::<NameEntry>();
+}
+
+fn pause_input(players: Query<&PlayerInput>, mut next_state: ResMut<NextState<GameState>>) {
+    if players.iter().any(|player_input| player_input.pause) {
+        next_state.set(GameState::Paused);
+    }
+}
+
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.enable_state_scoped_entities::<GameState>()
+            .add_systems(OnEnter(GameState::MainMenu), setup_main_menu)
+            .add_systems(OnEnter(GameState::Paused), setup_pause_menu)
+            .add_systems(
+                OnEnter(GameState::GameOver),
+                setup_game_over.before(stop_playback),
+            )
+            .add_systems(OnExit(GameState::GameOver), remove_name_entry)
+            .add_systems(
+                Update,
+                (
+                    menu_input,
+                    seed_field_input.run_if(in_state(GameState::GameOver)),
+                )
+                    .run_if(not(resource_exists::<NameEntry>)),
+            )
+            .add_systems(
+                Update,
+                // After the other menu inputs so that confirming the name doesn't restart the game
+                name_entry_input
+                    .after(menu_input)
+                    .run_if(resource_exists::<NameEntry>),
+            )
+            .add_systems(Update, pause_input.run_if(in_state(GameState::InGame)))
+            .add_systems(
+                Update,
+                (menu_options_input, update_menu_options_text)
+                    .chain()
+                    .run_if(in_state(GameState::MainMenu)),
+            );
+    }
+}
@@ -1,15 +1,30 @@
 use avian2d::prelude::*;
-use bevy::input::gamepad::{GamepadConnection, GamepadEvent};
 use bevy::prelude::*;
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+use bevy::render::render_asset::RenderAssetUsages;
 use bevy::render::render_resource::{AsBindGroup, ShaderRef};
 use bevy::sprite::{Material2d, Material2dPlugin};
 use bevy::sprite::{MaterialMesh2dBundle, Mesh2dHandle};
+use serde::{Deserialize, Serialize};
+
+use crate::asteroid::Asteroid;
+use crate::config::GameConfig;
+use crate::energy::LinkEnergy;
+use crate::health::{Health, Invulnerable};
+use crate::input::{PlayerDevice, PlayerInput};
+use crate::kind::{Chilled, CHILL_FACTOR};
+use crate::powerup::ActivePowerUps;
+use crate::{GameState, GameplaySet, RunEntity, StartRun};
 
 #[derive(PhysicsLayer)]
 pub enum GameLayer {
     Player,   // Layer 0
     Link,     // Layer 1
     Asteroid, // Layer 2
+    PowerUp,  // Layer 3
+    Spawner,  // Layer 4
+    /// Bodies of the solid asteroids, only colliding with each other
+    SolidAsteroid, // Layer 5
 }
 
 #[derive(Bundle)]
@@ -24,27 +39,314 @@ struct PlayerBundle {
     velocity: LinearVelocity,
     damping: LinearDamping,
     locked_axes: LockedAxes,
-    gamepad: PlayerGamepad,
+    device: PlayerDevice,
+    input: PlayerInput,
+    health: Health,
 }
 
 #[derive(Component, Clone, Copy, PartialEq, Eq)]
-pub struct PlayerId(u8);
+pub struct PlayerId(pub u8);
 
 #[derive(Component)]
 pub struct PlayerLink(Entity, Entity, Entity);
 
+impl PlayerLink {
+    pub fn players(&self) -> (Entity, Entity) {
+        (self.0, self.1)
+    }
+
+    pub fn collider(&self) -> Entity {
+        self.2
+    }
+
+    /// Player at the other end of the link, if `player` is linked by it
+    pub fn partner(&self, player: Entity) -> Option<Entity> {
+        if self.0 == player {
+            Some(self.1)
+        } else if self.1 == player {
+            Some(self.0)
+        } else {
+            None
+        }
+    }
+}
+
 #[derive(Component)]
 pub struct PlayerLinkCollider;
 
-#[derive(Component)]
-struct PlayerGamepad(Option<Gamepad>);
+/// Ship without health, drifting until a partner it was linked to revives it
+#[derive(Component, Debug)]
+pub struct Wreck {
+    /// Ships it was linked to, the only ones able to revive it
+    pub partners: Vec<Entity>,
+    /// Seconds a partner has spent close to the wreck
+    pub revive_progress: f32,
+}
+
+impl Wreck {
+    pub fn new(partners: Vec<Entity>) -> Self {
+        Self {
+            partners,
+            revive_progress: 0.0,
+        }
+    }
+}
+
+const PLAYER_DAMPING: f32 = 2.0;
+/// Wrecks keep drifting a lot longer than controlled ships
+const WRECK_DAMPING: f32 = 0.3;
+
+/// How the players are linked together
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LinkTopology {
+    /// Each player is linked to the next one
+    #[default]
+    Chain,
+    /// Like a chain but the last player is also linked to the first one
+    Ring,
+    /// Every player is linked to the first one
+    Star,
+}
+
+impl LinkTopology {
+    pub fn next(self) -> Self {
+        match self {
+            LinkTopology::Chain => LinkTopology::Ring,
+            LinkTopology::Ring => LinkTopology::Star,
+            LinkTopology::Star => LinkTopology::Chain,
+        }
+    }
+
+    /// Indices of the players linked together
+    fn pairs(self, number_players: u8) -> Vec<(usize, usize)> {
+        let n = number_players as usize;
+        match self {
+            LinkTopology::Chain => (1..n).map(|i| (i - 1, i)).collect(),
+            LinkTopology::Ring => {
+                let mut pairs = LinkTopology::Chain.pairs(number_players);
+                // With two players the ring would link them twice
+                if n > 2 {
+                    pairs.push((n - 1, 0));
+                }
+                pairs
+            }
+            LinkTopology::Star => (1..n).map(|i| (0, i)).collect(),
+        }
+    }
+}
+
+/// How the joint between two linked ships behaves
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LinkMode {
+    /// The ships can't get further apart than the maximum distance
+    #[default]
+    Leash,
+    /// The link stretches past the maximum distance and pulls the ships back like a rubber band
+    Spring,
+}
+
+/// Length of a link over time, a link pulled apart quickly whips for a short while
+#[derive(Component, Debug, Default)]
+pub struct LinkTension {
+    /// Distance between the two ships on the previous step
+    length: f32,
+    /// Seconds left of the whip
+    whip: f32,
+}
+
+impl LinkTension {
+    pub fn is_whipping(&self) -> bool {
+        self.whip > 0.0
+    }
+}
+
+/// Number of segments of the simulated rope of a link
+const ROPE_SEGMENTS: usize = 12;
+/// Share of its velocity a rope point keeps from one step to the next
+const ROPE_DAMPING: f32 = 0.96;
+const ROPE_ITERATIONS: usize = 8;
+
+/// Points of the rope between the two ships of a link, simulated with Verlet integration
+///
+/// The first and last points stick to the ships, the others follow with inertia so a slack link
+/// trails behind the ships and wraps around asteroids when it can't cut them.
+#[derive(Component, Debug)]
+pub struct LinkRope {
+    points: Vec<Vec2>,
+    /// Points on the previous step, their difference with `points` being the velocity
+    previous: Vec<Vec2>,
+}
+
+impl LinkRope {
+    /// Straight rope from `pos1` to `pos2`
+    fn new(pos1: Vec2, pos2: Vec2) -> Self {
+        let points = (0..=ROPE_SEGMENTS)
+            .map(|i| pos1.lerp(pos2, i as f32 / ROPE_SEGMENTS as f32))
+            .collect::<Vec<_>>();
+        Self {
+            previous: points.clone(),
+            points,
+        }
+    }
+
+    pub fn points(&self) -> &[Vec2] {
+        &self.points
+    }
+
+    /// Moves the rope one step forward, its ends held at `pos1` and `pos2`
+    ///
+    /// The segments only resist being stretched past their share of `length`, and the points are
+    /// pushed out of the `obstacles`, given as centers and radii.
+    fn step(&mut self, pos1: Vec2, pos2: Vec2, length: f32, obstacles: &[(Vec2, f32)]) {
+        let last = self.points.len() - 1;
+        for (point, previous) in self.points.iter_mut().zip(self.previous.iter_mut()) {
+            let velocity = (*point - *previous) * ROPE_DAMPING;
+            *previous = *point;
+            *point += velocity;
+        }
+        self.points[0] = pos1;
+        self.points[last] = pos2;
+
+        let segment_length = length / last as f32;
+        for _ in 0..ROPE_ITERATIONS {
+            for i in 0..last {
+                let delta = self.points[i + 1] - self.points[i];
+                let distance = delta.length();
+                if distance <= segment_length {
+                    continue;
+                }
+                let correction = delta * (distance - segment_length) / distance;
+                match (i == 0, i + 1 == last) {
+                    (true, true) => {}
+                    (true, false) => self.points[i + 1] -= correction,
+                    (false, true) => self.points[i] += correction,
+                    (false, false) => {
+                        self.points[i] += correction / 2.0;
+                        self.points[i + 1] -= correction / 2.0;
+                    }
+                }
+            }
+            for point in &mut self.points[1..last] {
+                for (center, radius) in obstacles {
+                    let offset = *point - *center;
+                    if offset.length_squared() < radius * radius {
+                        *point = *center + offset.normalize_or(Vec2::X) * *radius;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Distance a rope point moves before the link collider gets rebuilt
+const COLLIDER_REBUILD_DISTANCE: f32 = 0.5;
+
+/// Rope points, relative to the link collider, and radius the collider was last built from
+#[derive(Component, Debug)]
+struct LinkColliderShape {
+    points: Vec<Vec2>,
+    radius: f32,
+}
+
+impl LinkColliderShape {
+    fn new(rope: &LinkRope, origin: Vec2, radius: f32) -> Self {
+        Self {
+            points: rope.points.iter().map(|point| *point - origin).collect(),
+            radius,
+        }
+    }
+
+    /// Whether the rope moved or the link changed width enough for the collider to be rebuilt
+    fn is_outdated(&self, rope: &LinkRope, origin: Vec2, radius: f32) -> bool {
+        self.radius != radius
+            || self.points.iter().zip(&rope.points).any(|(built, point)| {
+                built.distance_squared(*point - origin) > COLLIDER_REBUILD_DISTANCE.powi(2)
+            })
+    }
+
+    /// Capsules along the segments
+    fn collider(&self) -> Collider {
+        Collider::compound(
+            self.points
+                .windows(2)
+                .map(|segment| {
+                    (
+                        Position::default(),
+                        Rotation::default(),
+                        Collider::capsule_endpoints(self.radius, segment[0], segment[1]),
+                    )
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Strip of quads `width` wide following `points`
+fn link_mesh(points: &[Vec2], width: f32) -> Mesh {
+    let last = points.len().saturating_sub(1).max(1) as f32;
+    let mut positions = Vec::with_capacity(points.len() * 2);
+    let mut uvs = Vec::with_capacity(points.len() * 2);
+    for (i, point) in points.iter().enumerate() {
+        let previous = points[i.saturating_sub(1)];
+        let next = points[(i + 1).min(points.len() - 1)];
+        let side = (next - previous).perp().normalize_or_zero() * width / 2.0;
+        let v = i as f32 / last;
+        positions.push((*point + side).extend(0.0).to_array());
+        positions.push((*point - side).extend(0.0).to_array());
+        uvs.push([0.0, v]);
+        uvs.push([1.0, v]);
+    }
+    let indices = (0..points.len().saturating_sub(1) as u32)
+        .flat_map(|i| {
+            let j = i * 2;
+            [j, j + 1, j + 2, j + 1, j + 3, j + 2]
+        })
+        .collect::<Vec<_>>();
+
+    Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::default(),
+    )
+    .with_inserted_attribute(
+        Mesh::ATTRIBUTE_NORMAL,
+        vec![[0.0, 0.0, 1.0]; positions.len()],
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
+    .with_inserted_indices(Indices::U32(indices))
+}
+
+pub const MIN_PLAYERS: u8 = 1;
+pub const MAX_PLAYERS: u8 = 8;
+
+/// Players setup used when starting a new game
+#[derive(Resource, Debug, Clone)]
+pub struct PlayersConfig {
+    pub number_players: u8,
+    pub topology: LinkTopology,
+    /// Device chosen for each player, players left to [`PlayerDevice::None`] get a free one
+    pub devices: [PlayerDevice; MAX_PLAYERS as usize],
+}
+
+impl Default for PlayersConfig {
+    fn default() -> Self {
+        Self {
+            number_players: 2,
+            topology: LinkTopology::default(),
+            devices: [PlayerDevice::None; MAX_PLAYERS as usize],
+        }
+    }
+}
 
 #[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
-struct CustomMaterial {
+pub struct CustomMaterial {
     #[uniform(0)]
-    color: LinearRgba,
+    pub color: LinearRgba,
 }
 
+/// Color of a fully charged link, way above 1 so it glows
+pub const LINK_COLOR: LinearRgba = LinearRgba::new(50.0, 190.0, 75.0, 1.0);
+
 impl Material2d for CustomMaterial {
     fn fragment_shader() -> ShaderRef {
         "shaders/animate_shader.wgsl".into()
@@ -58,18 +360,33 @@ fn setup_players(
     mut meshes: ResMut<Assets<Mesh>>,
     // mut materials: ResMut<Assets<ColorMaterial>>,
     mut materials: ResMut<Assets<CustomMaterial>>,
+    players_config: Res<PlayersConfig>,
+    config: Res<GameConfig>,
 ) {
-    let mut gamepads = gamepads.iter().collect::<Vec<_>>();
+    let number_players = players_config
+        .number_players
+        .clamp(MIN_PLAYERS, MAX_PLAYERS);
 
-    const SPAWN_RADIUS: f32 = 100.0;
-    let number_players = 2;
+    // Players without a chosen device get the free gamepads first, then the keyboard
+    let chosen_devices = &players_config.devices[..number_players as usize];
+    let mut free_gamepads = gamepads
+        .iter()
+        .map(PlayerDevice::Gamepad)
+        .filter(|device| !chosen_devices.contains(device));
+    let devices = chosen_devices
+        .iter()
+        .map(|device| match device {
+            PlayerDevice::None => free_gamepads.next().unwrap_or(PlayerDevice::Keyboard),
+            device => *device,
+        })
+        .collect::<Vec<_>>();
 
     let radius_step = 2.0 * std::f32::consts::PI / (number_players as f32);
     let players_entities = (0..number_players)
         .map(|i| {
             let angle_step = radius_step * (i as f32);
-            let pos_x = SPAWN_RADIUS * angle_step.cos();
-            let pos_y = SPAWN_RADIUS * angle_step.sin();
+            let pos_x = config.spawn_radius * angle_step.cos();
+            let pos_y = config.spawn_radius * angle_step.sin();
             (
                 commands
                     .spawn(PlayerBundle {
@@ -87,80 +404,113 @@ fn setup_players(
                         sensor: Sensor,
                         collision_layer: CollisionLayers::new(
                             GameLayer::Player,
-                            [GameLayer::Asteroid],
+                            [GameLayer::Asteroid, GameLayer::PowerUp],
                         ),
                         rigidbody: RigidBody::Dynamic,
-                        gamepad: PlayerGamepad(gamepads.pop()),
+                        device: devices[i as usize],
+                        input: PlayerInput::default(),
+                        health: Health(1.0),
                         mass: MassPropertiesBundle::new_computed(&Collider::circle(1.0), 1.0),
                         velocity: LinearVelocity(Vec2::ZERO),
-                        damping: LinearDamping(2.0),
+                        damping: LinearDamping(PLAYER_DAMPING),
                         locked_axes: LockedAxes::ROTATION_LOCKED,
                     })
+                    .insert(RunEntity)
                     .id(),
                 Vec2::new(pos_x, pos_y),
             )
         })
         .collect::<Vec<_>>();
 
-    for [(entity1, pos1), (entity2, pos2)] in players_entities.array_windows::<2>() {
-        const LINK_WIDTH: f32 = 10.0;
-        let translation = (*pos1 + (*pos2 - *pos1) / 2.0).extend(0.0);
-        let rotation = Quat::from_rotation_z(Vec2::Y.angle_between(*pos2 - *pos1));
-        let length = pos1.distance(*pos2);
-        let player_link_collider = commands
-            .spawn((
-                TransformBundle {
-                    local: Transform {
-                        translation,
-                        rotation,
-                        ..default()
-                    },
-                    ..default()
-                },
-                Collider::rectangle(LINK_WIDTH * 0.7, length),
-                Sensor,
-                CollisionLayers::new(GameLayer::Link, [GameLayer::Asteroid]),
-                PlayerLinkCollider,
-            ))
-            .id();
-
-        commands.spawn((
-            MaterialMesh2dBundle {
-                mesh: Mesh2dHandle(meshes.add(Rectangle::new(LINK_WIDTH, 1.0))),
-                material: materials.add(CustomMaterial {
-                    color: LinearRgba::new(50.0, 190.0, 75.0, 1.0),
-                }),
-                transform: Transform {
-                    translation,
-                    rotation,
-                    scale: Vec2::new(1.0, length).extend(0.0),
-                },
-                ..default()
+    for (i, j) in players_config.topology.pairs(number_players) {
+        spawn_link(
+            &mut commands,
+            &mut meshes,
+            &mut materials,
+            &config,
+            players_entities[i],
+            players_entities[j],
+        );
+    }
+}
+
+/// Spawns the link between two ships, its collider and the joint keeping them close
+fn spawn_link(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<CustomMaterial>,
+    config: &GameConfig,
+    (entity1, pos1): (Entity, Vec2),
+    (entity2, pos2): (Entity, Vec2),
+) {
+    // The link and its collider sit between the ships, their shapes relative to that point
+    let middle = (pos1 + pos2) / 2.0;
+    let rope = LinkRope::new(pos1, pos2);
+    let shape = LinkColliderShape::new(&rope, middle, config.link_width * 0.35);
+    let player_link_collider = commands
+        .spawn((
+            TransformBundle::from_transform(Transform::from_translation(middle.extend(0.0))),
+            shape.collider(),
+            shape,
+            Sensor,
+            CollisionLayers::new(GameLayer::Link, [GameLayer::Asteroid, GameLayer::Spawner]),
+            PlayerLinkCollider,
+            LinkTension {
+                length: pos1.distance(pos2),
+                whip: 0.0,
             },
-            PlayerLink(*entity1, *entity2, player_link_collider),
-        ));
+            RunEntity,
+        ))
+        .id();
 
-        const PLAYER_JOINT_DISTANCE: f32 = 200.0;
+    commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: Mesh2dHandle(
+                meshes.add(link_mesh(
+                    &rope
+                        .points
+                        .iter()
+                        .map(|point| *point - middle)
+                        .collect::<Vec<_>>(),
+                    config.link_width,
+                )),
+            ),
+            material: materials.add(CustomMaterial { color: LINK_COLOR }),
+            transform: Transform::from_translation(middle.extend(0.0)),
+            ..default()
+        },
+        PlayerLink(entity1, entity2, player_link_collider),
+        rope,
+        RunEntity,
+    ));
 
-        commands.spawn(DistanceJoint {
-            entity1: *entity1,
-            entity2: *entity2,
+    // A soft joint lets the ships stretch the link, and bounce back with less damping
+    let (compliance, damping_linear) = match config.link_mode {
+        LinkMode::Leash => (0.01, 10.0),
+        LinkMode::Spring => (config.link_spring_compliance, 1.0),
+    };
+    commands.spawn((
+        DistanceJoint {
+            entity1,
+            entity2,
             local_anchor1: Vec2::ZERO,
             local_anchor2: Vec2::ZERO,
             rest_length: 0.0,
             length_limits: Some(DistanceLimit {
                 min: 0.0,
-                max: PLAYER_JOINT_DISTANCE,
+                max: config.player_joint_distance,
             }),
-            damping_linear: 10.0,
+            damping_linear,
             damping_angular: 0.0,
             lagrange: 0.0, // TODO: I have no idea what that is
-            compliance: 0.01,
+            compliance,
             force: Vec2::ONE * 5.0,
-        });
-    }
+        },
+        RunEntity,
+    ));
 }
 
+/// Simulates the rope of every link, moving the link and its collider along
 fn link_follow_players(
     players: Query<
         &Transform,
@@ -171,23 +521,48 @@ fn link_follow_players(
         ),
     >,
     mut player_links: Query<
-        (&mut Transform, &PlayerLink),
+        (&mut Transform, &mut LinkRope, &PlayerLink),
         (
             Without<PlayerId>,
-            With<PlayerLink>,
             Without<PlayerLinkCollider>,
+            Without<Asteroid>,
         ),
     >,
     mut player_links_colliders: Query<
-        (&mut Transform, &mut Collider),
+        (
+            &mut Transform,
+            &mut Collider,
+            &mut LinkColliderShape,
+            &mut LinkTension,
+        ),
         (
             Without<PlayerId>,
             Without<PlayerLink>,
             With<PlayerLinkCollider>,
+            Without<Asteroid>,
         ),
     >,
+    asteroids: Query<(&Transform, &Asteroid)>,
+    power_ups: Res<ActivePowerUps>,
+    link_energy: Res<LinkEnergy>,
+    config: Res<GameConfig>,
+    time: Res<Time>,
 ) {
-    for (mut link_trans, link_info) in player_links.iter_mut() {
+    let rope_length = config.player_joint_distance * power_ups.joint_distance_factor();
+    let radius = config.link_width * 0.35 * power_ups.link_width_factor();
+    // A link that cuts the asteroids goes through them, otherwise it wraps around them
+    let obstacles = if link_energy.is_active() {
+        Vec::new()
+    } else {
+        asteroids
+            .iter()
+            .map(|(asteroid_trans, asteroid)| {
+                (asteroid_trans.translation.truncate(), asteroid.radius())
+            })
+            .collect::<Vec<_>>()
+    };
+
+    for (mut link_trans, mut rope, link_info) in player_links.iter_mut() {
         let Ok([player_trans1, player_trans2]) = players.get_many([link_info.0, link_info.1])
         else {
             continue;
@@ -196,98 +571,174 @@ fn link_follow_players(
         let pos1 = player_trans1.translation.truncate();
         let pos2 = player_trans2.translation.truncate();
         let length = pos1.distance(pos2);
-        let translation = (pos1 + (pos2 - pos1) / 2.0).extend(0.0);
-        let rotation = Quat::from_rotation_z(Vec2::Y.angle_between(pos2 - pos1));
-        link_trans.scale.y = length;
-
-        link_trans.rotation = rotation;
+        rope.step(pos1, pos2, rope_length, &obstacles);
 
-        link_trans.translation = translation;
+        let middle = (pos1 + pos2) / 2.0;
+        link_trans.translation = middle.extend(0.0);
 
-        let Ok((mut player_link_collider, mut player_link_col)) =
+        let Ok((mut player_link_collider, mut player_link_col, mut shape, mut tension)) =
             player_links_colliders.get_mut(link_info.2)
         else {
             continue;
         };
 
-        player_link_collider.translation = translation;
-        player_link_collider.rotation = rotation;
+        // Ships moving apart fast enough whip the link
+        let stretch_speed = (length - tension.length) / time.delta_seconds().max(f32::EPSILON);
+        tension.length = length;
+        tension.whip = if stretch_speed > config.link_whip_speed {
+            config.link_whip_duration
+        } else {
+            (tension.whip - time.delta_seconds()).max(0.0)
+        };
 
-        player_link_col.set_scale(Vec2::new(1.0, length), 4);
+        player_link_collider.translation = middle.extend(0.0);
+        // Rebuilding the compound collider is costly, a rope at rest keeps its collider
+        if shape.is_outdated(&rope, middle, radius) {
+            *shape = LinkColliderShape::new(&rope, middle, radius);
+            *player_link_col = shape.collider();
+        }
     }
 }
 
-fn gamepad_connect(
-    mut players: Query<&mut PlayerGamepad>,
-    mut evr_gamepad: EventReader<GamepadEvent>,
+/// Rebuilds the link meshes along their ropes
+fn bend_links(
+    links: Query<(&Transform, &LinkRope, &Mesh2dHandle), Changed<LinkRope>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    power_ups: Res<ActivePowerUps>,
+    config: Res<GameConfig>,
 ) {
-    for ev in evr_gamepad.read() {
-        let GamepadEvent::Connection(ev_conn) = ev else {
+    let width = config.link_width * power_ups.link_width_factor();
+    for (link_trans, rope, mesh_handle) in links.iter() {
+        let Some(mesh) = meshes.get_mut(&mesh_handle.0) else {
             continue;
         };
-        match &ev_conn.connection {
-            GamepadConnection::Connected(_info) => {
-                for mut player_gamepad in players.iter_mut() {
-                    if player_gamepad.0.is_none() {
-                        player_gamepad.0 = Some(ev_conn.gamepad);
-                        break;
-                    }
-                }
-            }
-            GamepadConnection::Disconnected => {
-                for mut player_gamepad in players.iter_mut() {
-                    if player_gamepad
-                        .0
-                        .is_some_and(|gamepad| gamepad == ev_conn.gamepad)
-                    {
-                        player_gamepad.0 = None;
-                    }
-                }
+        let middle = link_trans.translation.truncate();
+        let points = rope
+            .points()
+            .iter()
+            .map(|point| *point - middle)
+            .collect::<Vec<_>>();
+        *mesh = link_mesh(&points, width);
+    }
+}
+
+/// Partners staying close to a wreck bring it back with some health and linked to them again
+fn revive_wrecks(
+    mut commands: Commands,
+    mut wrecks: Query<(
+        Entity,
+        &Transform,
+        &mut Wreck,
+        &mut Health,
+        &mut Sprite,
+        &mut LinearDamping,
+    )>,
+    ships: Query<&Transform, (With<PlayerId>, Without<Wreck>)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<CustomMaterial>>,
+    config: Res<GameConfig>,
+    time: Res<Time>,
+) {
+    for (wreck_entity, wreck_trans, mut wreck, mut health, mut sprite, mut damping) in
+        wrecks.iter_mut()
+    {
+        let position = wreck_trans.translation.truncate();
+        let rescuers = wreck
+            .partners
+            .iter()
+            .filter_map(|&partner| {
+                ships
+                    .get(partner)
+                    .ok()
+                    .map(|partner_trans| (partner, partner_trans.translation.truncate()))
+            })
+            .collect::<Vec<_>>();
+
+        // Progress slowly fades away when the partners leave
+        let close = rescuers
+            .iter()
+            .any(|(_, pos)| pos.distance(position) < config.revive_distance);
+        wreck.revive_progress = if close {
+            wreck.revive_progress + time.delta_seconds()
+        } else {
+            (wreck.revive_progress - time.delta_seconds()).max(0.0)
+        };
+
+        if wreck.revive_progress < config.revive_duration {
+            // Wrecks are dimmed and light up as they get revived
+            let progress = wreck.revive_progress / config.revive_duration.max(f32::EPSILON);
+            let gray = 0.4 + 0.6 * progress;
+            sprite.color = Color::srgba(gray, gray, gray, 0.6 + 0.4 * progress);
+            if damping.0 != WRECK_DAMPING {
+                damping.0 = WRECK_DAMPING;
             }
+            continue;
+        }
+
+        health.0 = config.revive_health;
+        sprite.color = Color::WHITE;
+        damping.0 = PLAYER_DAMPING;
+        commands
+            .entity(wreck_entity)
+            .remove::<Wreck>()
+            .insert(Invulnerable(Timer::from_seconds(
+                config.invulnerability_duration,
+                TimerMode::Once,
+            )));
+        for rescuer in rescuers {
+            spawn_link(
+                &mut commands,
+                &mut meshes,
+                &mut materials,
+                &config,
+                (wreck_entity, position),
+                rescuer,
+            );
         }
     }
 }
 
-fn gamepad_input(
-    axes: Res<Axis<GamepadAxis>>,
-    buttons: Res<ButtonInput<GamepadButton>>,
-    mut players: Query<(&mut Transform, &mut LinearVelocity, &PlayerGamepad)>,
+fn player_movement(
+    mut players: Query<
+        (
+            &mut Transform,
+            &mut LinearVelocity,
+            &PlayerInput,
+            Has<Chilled>,
+        ),
+        Without<Wreck>,
+    >,
 ) {
-    for (mut player_transform, mut player_velocity, player_gamepad) in players.iter_mut() {
-        if let Some(gamepad) = player_gamepad.0 {
-            let axis_lx = GamepadAxis {
-                gamepad,
-                axis_type: GamepadAxisType::LeftStickX,
-            };
-            let axis_ly = GamepadAxis {
-                gamepad,
-                axis_type: GamepadAxisType::LeftStickY,
-            };
+    for (mut player_transform, mut player_velocity, player_input, chilled) in players.iter_mut() {
+        if let Some(aim) = player_input.aim {
+            player_transform.rotation =
+                Quat::from_rotation_z(aim.to_angle() - std::f32::consts::FRAC_PI_2);
+        }
 
-            if let (Some(x), Some(y)) = (axes.get(axis_lx), axes.get(axis_ly)) {
-                let left_stick = Vec2::new(x, y);
+        if player_input.thrust {
+            const PLAYER_ACCELERATION: f32 = 20.0;
+            const PLAYER_BOOST_FACTOR: f32 = 2.0;
+            const MAX_PLAYER_SPEED: f32 = 1000.0;
 
-                const GAMEPAD_DEADZONE: f32 = 0.1;
-                if left_stick.length() > GAMEPAD_DEADZONE {
-                    player_transform.rotation =
-                        Quat::from_rotation_z(left_stick.to_angle() - std::f32::consts::FRAC_PI_2);
-                }
+            let mut acceleration = if player_input.boost {
+                PLAYER_ACCELERATION * PLAYER_BOOST_FACTOR
+            } else {
+                PLAYER_ACCELERATION
+            };
+            if chilled {
+                acceleration *= CHILL_FACTOR;
             }
 
-            let forward_button = GamepadButton {
-                gamepad,
-                button_type: GamepadButtonType::South,
-            };
+            player_velocity.0 += Vec2::from_angle(
+                player_transform.rotation.to_scaled_axis().z + std::f32::consts::FRAC_PI_2,
+            ) * acceleration;
+            player_velocity.0 = player_velocity.clamp_length(0.0, MAX_PLAYER_SPEED);
+        }
 
-            if buttons.pressed(forward_button) {
-                const PLAYER_ACCELERATION: f32 = 20.0;
-                const MAX_PLAYER_SPEED: f32 = 1000.0;
+        if player_input.brake {
+            const PLAYER_BRAKE_FACTOR: f32 = 0.9;
 
-                player_velocity.0 += Vec2::from_angle(
-                    player_transform.rotation.to_scaled_axis().z + std::f32::consts::FRAC_PI_2,
-                ) * PLAYER_ACCELERATION;
-                player_velocity.0 = player_velocity.clamp_length(0.0, MAX_PLAYER_SPEED);
-            }
+            player_velocity.0 *= PLAYER_BRAKE_FACTOR;
         }
     }
 }
@@ -296,10 +747,18 @@ pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, setup_players)
+        app.init_resource::<PlayersConfig>()
+            .add_systems(StartRun, setup_players)
             .add_plugins(Material2dPlugin::<CustomMaterial>::default())
-            .add_systems(Update, gamepad_input)
-            .add_systems(Update, gamepad_connect)
-            .add_systems(Update, link_follow_players);
+            .add_systems(
+                FixedUpdate,
+                (
+                    player_movement.in_set(GameplaySet::Controls),
+                    revive_wrecks.in_set(GameplaySet::Revive),
+                )
+                    .run_if(in_state(GameState::InGame)),
+            )
+            .add_systems(FixedUpdate, link_follow_players.in_set(GameplaySet::Link))
+            .add_systems(Update, bend_links);
     }
 }
@@ -0,0 +1,359 @@
+use std::collections::HashSet;
+
+use avian2d::prelude::*;
+use bevy::prelude::*;
+use bevy_prng::WyRand;
+use bevy_rand::resource::GlobalEntropy;
+use rand_core::RngCore;
+use serde::{Deserialize, Serialize};
+
+use crate::asteroid::AsteroidDestroyed;
+use crate::config::GameConfig;
+use crate::health::Health;
+use crate::player::{GameLayer, PlayerId, Wreck};
+use crate::replay::Playback;
+use crate::{GameState, GameplaySet, RunEntity, StartRun};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerUpKind {
+    /// Raises the maximum distance between linked ships
+    LongerLink,
+    /// Widens the link collider so it sweeps more asteroids
+    WiderLink,
+    /// Asteroids break on the ships without hurting them
+    Shield,
+    /// Slows the whole game down
+    SlowMotion,
+    /// Refills part of the health of every ship, right away
+    Heal,
+}
+
+impl PowerUpKind {
+    fn name(self) -> &'static str {
+        match self {
+            PowerUpKind::LongerLink => "Longer link",
+            PowerUpKind::WiderLink => "Wider link",
+            PowerUpKind::Shield => "Shield",
+            PowerUpKind::SlowMotion => "Slow motion",
+            PowerUpKind::Heal => "Heal",
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            PowerUpKind::LongerLink => Color::srgb(0.2, 0.8, 0.3),
+            PowerUpKind::WiderLink => Color::srgb(0.9, 0.8, 0.2),
+            PowerUpKind::Shield => Color::srgb(0.3, 0.7, 1.0),
+            PowerUpKind::SlowMotion => Color::srgb(0.7, 0.4, 1.0),
+            PowerUpKind::Heal => Color::srgb(1.0, 0.3, 0.4),
+        }
+    }
+}
+
+const LONGER_LINK_FACTOR: f32 = 1.5;
+const WIDER_LINK_FACTOR: f32 = 2.5;
+const SLOW_MOTION_SPEED: f32 = 0.5;
+const HEAL_AMOUNT: f32 = 0.3;
+const PICKUP_SIZE: f32 = 20.0;
+
+/// Pickup left behind by a destroyed asteroid, collected by flying a ship over it
+#[derive(Component)]
+struct PowerUp {
+    kind: PowerUpKind,
+    /// The pickup disappears once the timer finishes
+    lifetime: Timer,
+}
+
+/// Timed power-ups currently affecting every ship, with their remaining seconds
+#[derive(Resource, Debug, Default)]
+pub struct ActivePowerUps(Vec<(PowerUpKind, f32)>);
+
+impl ActivePowerUps {
+    pub fn is_active(&self, kind: PowerUpKind) -> bool {
+        self.0.iter().any(|(active, _)| *active == kind)
+    }
+
+    /// Collecting a power-up that is already active starts its duration over
+    fn activate(&mut self, kind: PowerUpKind, duration: f32) {
+        match self.0.iter_mut().find(|(active, _)| *active == kind) {
+            Some((_, remaining)) => *remaining = duration,
+            None => self.0.push((kind, duration)),
+        }
+    }
+
+    pub fn joint_distance_factor(&self) -> f32 {
+        if self.is_active(PowerUpKind::LongerLink) {
+            LONGER_LINK_FACTOR
+        } else {
+            1.0
+        }
+    }
+
+    pub fn link_width_factor(&self) -> f32 {
+        if self.is_active(PowerUpKind::WiderLink) {
+            WIDER_LINK_FACTOR
+        } else {
+            1.0
+        }
+    }
+
+    pub fn time_speed(&self) -> f32 {
+        if self.is_active(PowerUpKind::SlowMotion) {
+            SLOW_MOTION_SPEED
+        } else {
+            1.0
+        }
+    }
+}
+
+fn reset_power_ups(mut active: ResMut<ActivePowerUps>) {
+    active.0.clear();
+}
+
+/// Destroyed asteroids may leave a pickup, each kind with its own chance
+fn drop_power_ups(
+    mut commands: Commands,
+    mut destroyed_reader: EventReader<AsteroidDestroyed>,
+    mut rng: ResMut<GlobalEntropy<WyRand>>,
+    config: Res<GameConfig>,
+) {
+    for destroyed in destroyed_reader.read() {
+        let roll = (rng.next_u32() as f32) / (u32::MAX as f32);
+        let mut chance = 0.0;
+        let Some(kind) = config
+            .power_up_drop_chances
+            .iter()
+            .find_map(|(kind, kind_chance)| {
+                chance += kind_chance;
+                (roll < chance).then_some(*kind)
+            })
+        else {
+            continue;
+        };
+
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    custom_size: Some(Vec2::splat(PICKUP_SIZE)),
+                    color: kind.color(),
+                    ..default()
+                },
+                transform: Transform::from_translation(destroyed.position.extend(5.0))
+                    .with_rotation(Quat::from_rotation_z(std::f32::consts::FRAC_PI_4)),
+                ..default()
+            },
+            Collider::circle(PICKUP_SIZE / 2.0),
+            Sensor,
+            CollisionLayers::new(GameLayer::PowerUp, [GameLayer::Player]),
+            RigidBody::Static,
+            PowerUp {
+                kind,
+                lifetime: Timer::from_seconds(config.power_up_lifetime, TimerMode::Once),
+            },
+            RunEntity,
+        ));
+    }
+}
+
+fn collect_power_ups(
+    mut commands: Commands,
+    mut collision_event_reader: EventReader<Collision>,
+    power_ups: Query<&PowerUp>,
+    mut ships: Query<&mut Health, (With<PlayerId>, Without<Wreck>)>,
+    mut active: ResMut<ActivePowerUps>,
+    config: Res<GameConfig>,
+) {
+    // Two ships touching the same pickup only collect it once
+    let mut collected = HashSet::new();
+
+    for Collision(contacts) in collision_event_reader.read() {
+        let (pickup, power_up) = match (
+            power_ups.get(contacts.entity1),
+            power_ups.get(contacts.entity2),
+        ) {
+            (Ok(power_up), Err(_)) if ships.contains(contacts.entity2) => {
+                (contacts.entity1, power_up)
+            }
+            (Err(_), Ok(power_up)) if ships.contains(contacts.entity1) => {
+                (contacts.entity2, power_up)
+            }
+            _ => continue,
+        };
+        if !collected.insert(pickup) {
+            continue;
+        }
+        commands.entity(pickup).despawn_recursive();
+
+        match power_up.kind {
+            PowerUpKind::Heal => {
+                for mut health in ships.iter_mut() {
+                    health.0 = (health.0 + HEAL_AMOUNT).min(1.0);
+                }
+            }
+            kind => active.activate(kind, config.power_up_duration),
+        }
+    }
+}
+
+fn tick_power_ups(
+    mut commands: Commands,
+    mut active: ResMut<ActivePowerUps>,
+    mut pickups: Query<(Entity, &mut PowerUp, &mut Visibility)>,
+    time: Res<Time>,
+) {
+    const BLINK_TIME: f32 = 3.0;
+    const BLINK_PERIOD: f32 = 0.15;
+
+    active.0.retain_mut(|(_, remaining)| {
+        *remaining -= time.delta_seconds();
+        *remaining > 0.0
+    });
+
+    for (pickup, mut power_up, mut visibility) in pickups.iter_mut() {
+        if power_up.lifetime.tick(time.delta()).finished() {
+            commands.entity(pickup).despawn_recursive();
+            continue;
+        }
+        // Pickups about to disappear blink
+        let left = power_up.lifetime.remaining_secs();
+        let visible = left > BLINK_TIME || (left / BLINK_PERIOD) as u32 % 2 == 0;
+        let blink = if visible {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+        if *visibility != blink {
+            *visibility = blink;
+        }
+    }
+}
+
+/// Keeps the length of the joints matching the config and the longer link power-up
+fn apply_longer_link(
+    mut joints: Query<&mut DistanceJoint>,
+    active: Res<ActivePowerUps>,
+    config: Res<GameConfig>,
+) {
+    let max = config.player_joint_distance * active.joint_distance_factor();
+    for mut joint in joints.iter_mut() {
+        if let Some(limits) = joint.length_limits.as_mut() {
+            if limits.max != max {
+                limits.max = max;
+            }
+        }
+    }
+}
+
+/// Slows the virtual clock while slow motion is active, the replay takes care of it during a
+/// playback
+fn apply_slow_motion(
+    active: Res<ActivePowerUps>,
+    state: Res<State<GameState>>,
+    mut time: ResMut<Time<Virtual>>,
+) {
+    let speed = if *state.get() == GameState::InGame {
+        active.time_speed()
+    } else {
+        1.0
+    };
+    if time.relative_speed() != speed {
+        time.set_relative_speed(speed);
+    }
+}
+
+/// Shielded ships get a blue tint
+fn show_shield(
+    mut ships: Query<&mut Sprite, (With<PlayerId>, Without<Wreck>)>,
+    active: Res<ActivePowerUps>,
+) {
+    let color = if active.is_active(PowerUpKind::Shield) {
+        PowerUpKind::Shield.color()
+    } else {
+        Color::WHITE
+    };
+    for mut sprite in ships.iter_mut() {
+        if sprite.color != color {
+            sprite.color = color;
+        }
+    }
+}
+
+#[derive(Component)]
+struct PowerUpText;
+
+fn setup_power_up_ui(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 30.0,
+                ..default()
+            },
+        )
+        .with_text_justify(JustifyText::Right)
+        // Under the score, the bottom corners being used by the replay and game over texts
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            right: Val::Px(40.0),
+            top: Val::Px(90.0),
+            ..default()
+        }),
+        PowerUpText,
+        RunEntity,
+    ));
+}
+
+fn update_power_up_ui(
+    mut text_q: Query<&mut Text, With<PowerUpText>>,
+    active: Res<ActivePowerUps>,
+) {
+    if !active.is_changed() {
+        return;
+    }
+    let sections = active
+        .0
+        .iter()
+        .map(|(kind, remaining)| {
+            TextSection::new(
+                format!("{} {:.1}s\n", kind.name(), remaining),
+                TextStyle {
+                    font_size: 30.0,
+                    color: kind.color(),
+                    ..default()
+                },
+            )
+        })
+        .collect::<Vec<_>>();
+    for mut text in text_q.iter_mut() {
+        text.sections.clone_from(&sections);
+    }
+}
+
+pub struct PowerUpPlugin;
+
+impl Plugin for PowerUpPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ActivePowerUps>()
+            .add_systems(StartRun, (reset_power_ups, setup_power_up_ui))
+            .add_systems(
+                FixedUpdate,
+                (
+                    drop_power_ups,
+                    collect_power_ups,
+                    tick_power_ups,
+                    apply_longer_link,
+                )
+                    .chain()
+                    .in_set(GameplaySet::PowerUps)
+                    .run_if(in_state(GameState::InGame)),
+            )
+            .add_systems(
+                Update,
+                (
+                    apply_slow_motion.run_if(not(resource_exists::<Playback>)),
+                    show_shield,
+                    update_power_up_ui,
+                ),
+            );
+    }
+}
@@ -0,0 +1,274 @@
+use bevy::prelude::*;
+
+use crate::input::{Action, GamepadStick, InputBindings};
+use crate::player::MAX_PLAYERS;
+use crate::GameState;
+
+/// Kind of device whose bindings are being edited
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum RemapDevice {
+    #[default]
+    Keyboard,
+    Gamepad,
+    Mouse,
+}
+
+impl RemapDevice {
+    fn next(self) -> Self {
+        match self {
+            RemapDevice::Keyboard => RemapDevice::Gamepad,
+            RemapDevice::Gamepad => RemapDevice::Mouse,
+            RemapDevice::Mouse => RemapDevice::Keyboard,
+        }
+    }
+
+    fn rows(self) -> Vec<RemapRow> {
+        let mut rows = match self {
+            RemapDevice::Keyboard => (0..4).map(RemapRow::RotateKey).collect(),
+            RemapDevice::Gamepad => vec![RemapRow::RotateStick, RemapRow::Deadzone],
+            RemapDevice::Mouse => vec![],
+        };
+        rows.extend(Action::BUTTONS.map(RemapRow::Button));
+        rows
+    }
+}
+
+/// A line of the remapping screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RemapRow {
+    /// Up, left, down or right key used to aim
+    RotateKey(usize),
+    RotateStick,
+    Deadzone,
+    Button(Action),
+}
+
+#[derive(Resource, Debug, Default)]
+struct RemapCursor {
+    player: usize,
+    device: RemapDevice,
+    row: usize,
+    /// Waiting for the key or button to bind to the selected row
+    listening: bool,
+}
+
+#[derive(Component)]
+struct RemapText;
+
+fn setup_remap_screen(mut commands: Commands) {
+    commands.insert_resource(RemapCursor::default());
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 36.0,
+                color: Color::srgba(0.9, 0.9, 0.9, 1.0),
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(80.0),
+            top: Val::Px(80.0),
+            ..default()
+        }),
+        RemapText,
+        StateScoped(GameState::Bindings),
+    ));
+}
+
+fn save_bindings(bindings: Res<InputBindings>) {
+    bindings.save();
+}
+
+fn remap_navigation(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut cursor: ResMut<RemapCursor>,
+    mut bindings: ResMut<InputBindings>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if cursor.listening {
+        return;
+    }
+
+    let rows = cursor.device.rows();
+    if keys.just_pressed(KeyCode::Escape) {
+        next_state.set(GameState::MainMenu);
+    } else if keys.just_pressed(KeyCode::ArrowUp) {
+        cursor.row = (cursor.row + rows.len() - 1) % rows.len();
+    } else if keys.just_pressed(KeyCode::ArrowDown) {
+        cursor.row = (cursor.row + 1) % rows.len();
+    } else if keys.just_pressed(KeyCode::ArrowLeft) {
+        cursor.player = (cursor.player + MAX_PLAYERS as usize - 1) % MAX_PLAYERS as usize;
+    } else if keys.just_pressed(KeyCode::ArrowRight) {
+        cursor.player = (cursor.player + 1) % MAX_PLAYERS as usize;
+    } else if keys.just_pressed(KeyCode::Tab) {
+        cursor.device = cursor.device.next();
+        cursor.row = 0;
+    }
+
+    let player = &mut bindings.players[cursor.player];
+    match (rows[cursor.row], cursor.device) {
+        (RemapRow::RotateStick, _) if keys.just_pressed(KeyCode::Enter) => {
+            player.rotate_stick = match player.rotate_stick {
+                GamepadStick::Left => GamepadStick::Right,
+                GamepadStick::Right => GamepadStick::Left,
+            };
+        }
+        (RemapRow::Deadzone, _) => {
+            const DEADZONE_STEP: f32 = 0.05;
+            if keys.just_pressed(KeyCode::Equal) {
+                player.deadzone = (player.deadzone + DEADZONE_STEP).min(0.9);
+            } else if keys.just_pressed(KeyCode::Minus) {
+                player.deadzone = (player.deadzone - DEADZONE_STEP).max(0.0);
+            }
+        }
+        (RemapRow::Button(action), device) if keys.just_pressed(KeyCode::Delete) => match device {
+            RemapDevice::Keyboard => {
+                player.keyboard.remove(&action);
+            }
+            RemapDevice::Gamepad => {
+                player.gamepad.remove(&action);
+            }
+            RemapDevice::Mouse => {
+                player.mouse.remove(&action);
+            }
+        },
+        (RemapRow::RotateKey(_) | RemapRow::Button(_), _) if keys.just_pressed(KeyCode::Enter) => {
+            cursor.listening = true;
+        }
+        _ => {}
+    }
+}
+
+/// Binds the next key or button pressed to the selected row
+fn remap_listen(
+    keys: Res<ButtonInput<KeyCode>>,
+    buttons: Res<ButtonInput<GamepadButton>>,
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    mut cursor: ResMut<RemapCursor>,
+    mut bindings: ResMut<InputBindings>,
+) {
+    // Skip the frame the listening started, the confirm key is still just pressed
+    if !cursor.listening || cursor.is_changed() {
+        return;
+    }
+
+    let row = cursor.device.rows()[cursor.row];
+    let player = &mut bindings.players[cursor.player];
+
+    if keys.just_pressed(KeyCode::Escape) {
+        cursor.listening = false;
+        return;
+    }
+
+    match (cursor.device, row) {
+        (RemapDevice::Keyboard, row) => {
+            let Some(key) = keys.get_just_pressed().next() else {
+                return;
+            };
+            match row {
+                RemapRow::RotateKey(0) => player.rotate_keys.up = *key,
+                RemapRow::RotateKey(1) => player.rotate_keys.left = *key,
+                RemapRow::RotateKey(2) => player.rotate_keys.down = *key,
+                RemapRow::RotateKey(_) => player.rotate_keys.right = *key,
+                RemapRow::Button(action) => {
+                    player.keyboard.insert(action, *key);
+                }
+                _ => {}
+            }
+        }
+        (RemapDevice::Gamepad, RemapRow::Button(action)) => {
+            let Some(button) = buttons.get_just_pressed().next() else {
+                return;
+            };
+            player.gamepad.insert(action, button.button_type);
+        }
+        (RemapDevice::Mouse, RemapRow::Button(action)) => {
+            let Some(button) = mouse_buttons.get_just_pressed().next() else {
+                return;
+            };
+            player.mouse.insert(action, *button);
+        }
+        _ => {}
+    }
+
+    cursor.listening = false;
+}
+
+fn update_remap_text(
+    cursor: Res<RemapCursor>,
+    bindings: Res<InputBindings>,
+    mut text_q: Query<&mut Text, With<RemapText>>,
+) {
+    const ROTATE_KEYS: [&str; 4] = ["up", "left", "down", "right"];
+    let player = &bindings.players[cursor.player];
+
+    let rows = cursor
+        .device
+        .rows()
+        .into_iter()
+        .enumerate()
+        .map(|(i, row)| {
+            let (name, value) = match row {
+                RemapRow::RotateKey(k) => {
+                    let keys = player.rotate_keys;
+                    let key = [keys.up, keys.left, keys.down, keys.right][k];
+                    (format!("Rotate {}", ROTATE_KEYS[k]), format!("{key:?}"))
+                }
+                RemapRow::RotateStick => (
+                    "Rotate".to_string(),
+                    format!("{:?} stick", player.rotate_stick),
+                ),
+                RemapRow::Deadzone => ("Deadzone".to_string(), format!("{:.2}", player.deadzone)),
+                RemapRow::Button(action) => {
+                    let value = match cursor.device {
+                        RemapDevice::Keyboard => {
+                            player.keyboard.get(&action).map(|b| format!("{b:?}"))
+                        }
+                        RemapDevice::Gamepad => {
+                            player.gamepad.get(&action).map(|b| format!("{b:?}"))
+                        }
+                        RemapDevice::Mouse => player.mouse.get(&action).map(|b| format!("{b:?}")),
+                    };
+                    (
+                        format!("{action:?}"),
+                        value.unwrap_or_else(|| "-".to_string()),
+                    )
+                }
+            };
+
+            let selected = i == cursor.row;
+            let value = if selected && cursor.listening {
+                "...".to_string()
+            } else {
+                value
+            };
+            format!("{} {name}: {value}\n", if selected { ">" } else { " " })
+        })
+        .collect::<String>();
+
+    for mut text in text_q.iter_mut() {
+        text.sections[0].value = format!(
+            "BINDINGS\n\n[Left/Right] Player {}\n[Tab] Device: {:?}\n\n{rows}\n\
+            [Up/Down] Select, [Enter] Rebind, [Delete] Unbind, [-/=] Deadzone, [Escape] Save and go back",
+            cursor.player + 1,
+            cursor.device,
+        );
+    }
+}
+
+pub struct RemapPlugin;
+
+impl Plugin for RemapPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::Bindings), setup_remap_screen)
+            .add_systems(OnExit(GameState::Bindings), save_bindings)
+            .add_systems(
+                Update,
+                (remap_navigation, remap_listen, update_remap_text)
+                    .chain()
+                    .run_if(in_state(GameState::Bindings)),
+            );
+    }
+}
@@ -0,0 +1,508 @@
+use std::f32::consts::TAU;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::arena::{ArenaBounds, AsteroidBounds, PlayerBounds};
+use crate::input::{ApplyInputSet, PlayerInput};
+use crate::player::{LinkTopology, PlayerId, PlayersConfig};
+use crate::powerup::ActivePowerUps;
+use crate::{cli_arg, restart_run, GameState, RunEntity, RunSeed, StartRun, FIXED_HZ};
+
+/// Input of a player during a fixed step, packed in 16 bits
+///
+/// The 10 lowest bits hold the aim angle, the next ones whether the player aims, thrusts, brakes,
+/// boosts and holds the link button.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct PackedInput(u16);
+
+const AIM_STEPS: u16 = 1 << 10;
+const AIM_BIT: u16 = 1 << 10;
+const THRUST_BIT: u16 = 1 << 11;
+const BRAKE_BIT: u16 = 1 << 12;
+const BOOST_BIT: u16 = 1 << 13;
+const TOGGLE_LINK_BIT: u16 = 1 << 14;
+
+impl PackedInput {
+    fn pack(input: &PlayerInput) -> Self {
+        let mut bits = 0;
+        if let Some(aim) = input.aim {
+            let turns = (aim.to_angle() / TAU).rem_euclid(1.0);
+            bits |= AIM_BIT | (turns * AIM_STEPS as f32).round() as u16 % AIM_STEPS;
+        }
+        for (pressed, bit) in [
+            (input.thrust, THRUST_BIT),
+            (input.brake, BRAKE_BIT),
+            (input.boost, BOOST_BIT),
+            (input.toggle_link, TOGGLE_LINK_BIT),
+        ] {
+            if pressed {
+                bits |= bit;
+            }
+        }
+        Self(bits)
+    }
+
+    /// Input described by the packed bits, with a rounded aim
+    fn unpack(self, pause: bool) -> PlayerInput {
+        let aim = (self.0 & AIM_BIT != 0)
+            .then(|| Vec2::from_angle((self.0 % AIM_STEPS) as f32 / AIM_STEPS as f32 * TAU));
+        PlayerInput {
+            aim,
+            thrust: self.0 & THRUST_BIT != 0,
+            brake: self.0 & BRAKE_BIT != 0,
+            boost: self.0 & BOOST_BIT != 0,
+            toggle_link: self.0 & TOGGLE_LINK_BIT != 0,
+            pause,
+        }
+    }
+}
+
+/// Everything needed to play a game again: its seed, its setup and the inputs of every step
+///
+/// The tuning files are not part of the replay, it only matches the game while they are unchanged.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Replay {
+    seed: u64,
+    number_players: u8,
+    topology: LinkTopology,
+    asteroid_bounds: AsteroidBounds,
+    player_bounds: PlayerBounds,
+    /// Inputs of every player, identical consecutive steps being stored once with their count
+    steps: Vec<(u32, Vec<PackedInput>)>,
+}
+
+impl Replay {
+    /// File where the last game played is saved
+    fn last_path() -> PathBuf {
+        dirs::data_dir()
+            .unwrap_or_default()
+            .join("bevy_asteroid")
+            .join("replays")
+            .join("last.replay.ron")
+    }
+
+    fn push(&mut self, inputs: Vec<PackedInput>) {
+        match self.steps.last_mut() {
+            Some((count, last)) if *last == inputs => *count += 1,
+            _ => self.steps.push((1, inputs)),
+        }
+    }
+
+    fn len(&self) -> u32 {
+        self.steps.iter().map(|(count, _)| count).sum()
+    }
+
+    fn load(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+        ron::from_str(&content).map_err(|err| err.to_string())
+    }
+
+    fn save(&self, path: &Path) {
+        let content = match ron::to_string(self) {
+            Ok(content) => content,
+            Err(err) => {
+                error!("Could not serialize the replay: {err}");
+                return;
+            }
+        };
+
+        if let Err(err) = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(path, content))
+        {
+            error!("Could not save the replay to {}: {err}", path.display());
+        } else {
+            info!("Replay saved to {}", path.display());
+        }
+    }
+}
+
+/// Inputs of the game being played, saved once it is over
+#[derive(Resource)]
+struct Recording(Replay);
+
+/// Replay played instead of the players' devices
+#[derive(Resource)]
+pub struct Playback {
+    replay: Replay,
+    length: u32,
+    /// Number of steps already played
+    step: u32,
+    /// Position of the next step in the replay steps
+    run: usize,
+    run_offset: u32,
+    fast_forward: bool,
+    /// Step to reach as fast as possible
+    seek: Option<u32>,
+}
+
+impl Playback {
+    fn new(replay: Replay) -> Self {
+        Self {
+            length: replay.len(),
+            replay,
+            step: 0,
+            run: 0,
+            run_offset: 0,
+            fast_forward: false,
+            seek: None,
+        }
+    }
+
+    fn rewind(&mut self) {
+        self.step = 0;
+        self.run = 0;
+        self.run_offset = 0;
+    }
+
+    /// Inputs of the next step, `None` once the replay is over
+    fn next(&mut self) -> Option<&[PackedInput]> {
+        let (count, inputs) = self.replay.steps.get(self.run)?;
+        self.step += 1;
+        self.run_offset += 1;
+        if self.run_offset == *count {
+            self.run += 1;
+            self.run_offset = 0;
+        }
+        Some(inputs)
+    }
+}
+
+/// Sets the game up like the replay and starts playing it
+fn start_playback(world: &mut World, replay: Replay) {
+    let mut players_config = world.resource_mut::<PlayersConfig>();
+    players_config.number_players = replay.number_players;
+    players_config.topology = replay.topology;
+
+    let mut bounds = world.resource_mut::<ArenaBounds>();
+    bounds.asteroids = replay.asteroid_bounds;
+    bounds.players = replay.player_bounds;
+
+    world.resource_mut::<RunSeed>().0 = replay.seed;
+    world.insert_resource(Playback::new(replay));
+    world
+        .resource_mut::<NextState<GameState>>()
+        .set(GameState::InGame);
+}
+
+/// Plays the replay given with `--replay`
+fn play_replay_arg(mut commands: Commands) {
+    let Some(path) = cli_arg("--replay") else {
+        return;
+    };
+    match Replay::load(Path::new(&path)) {
+        Ok(replay) => commands.add(move |world: &mut World| start_playback(world, replay)),
+        Err(err) => {
+            eprintln!("--replay expects a replay file, {path}: {err}");
+            std::process::exit(2);
+        }
+    }
+}
+
+fn watch_last_replay(mut commands: Commands, keys: Res<ButtonInput<KeyCode>>) {
+    if !keys.just_pressed(KeyCode::KeyV) {
+        return;
+    }
+    let path = Replay::last_path();
+    match Replay::load(&path) {
+        Ok(replay) => commands.add(move |world: &mut World| start_playback(world, replay)),
+        Err(err) => warn!("Could not load the replay {}: {err}", path.display()),
+    }
+}
+
+fn reset_replay(
+    mut commands: Commands,
+    playback: Option<ResMut<Playback>>,
+    run_seed: Res<RunSeed>,
+    players_config: Res<PlayersConfig>,
+    bounds: Res<ArenaBounds>,
+) {
+    match playback {
+        Some(mut playback) => {
+            playback.rewind();
+            commands.remove_resource::<Recording>();
+        }
+        None => commands.insert_resource(Recording(Replay {
+            seed: run_seed.0,
+            number_players: players_config.number_players,
+            topology: players_config.topology,
+            asteroid_bounds: bounds.asteroids,
+            player_bounds: bounds.players,
+            steps: Vec::new(),
+        })),
+    }
+}
+
+fn record_inputs(
+    mut recording: ResMut<Recording>,
+    mut players: Query<(&PlayerId, &mut PlayerInput)>,
+) {
+    let mut inputs = vec![PackedInput::default(); players.iter().len()];
+    for (player_id, mut player_input) in players.iter_mut() {
+        let packed = PackedInput::pack(&player_input);
+        // Play with the rounded input so that the replay matches the game exactly
+        *player_input = packed.unpack(player_input.pause);
+        if let Some(input) = inputs.get_mut(player_id.0 as usize) {
+            *input = packed;
+        }
+    }
+    recording.0.push(inputs);
+}
+
+fn play_inputs(
+    mut playback: ResMut<Playback>,
+    mut players: Query<(&PlayerId, &mut PlayerInput)>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let Some(inputs) = playback.next() else {
+        next_state.set(GameState::GameOver);
+        return;
+    };
+    for (player_id, mut player_input) in players.iter_mut() {
+        let packed = inputs
+            .get(player_id.0 as usize)
+            .copied()
+            .unwrap_or_default();
+        *player_input = packed.unpack(player_input.pause);
+    }
+
+    if playback.seek.is_some_and(|seek| seek <= playback.step) {
+        playback.seek = None;
+    }
+}
+
+fn save_recording(mut commands: Commands, recording: Option<Res<Recording>>) {
+    let Some(recording) = recording else {
+        return;
+    };
+    recording.0.save(&Replay::last_path());
+    commands.remove_resource::<Recording>();
+}
+
+const DEFAULT_MAX_DELTA: Duration = Duration::from_millis(250);
+
+pub fn stop_playback(mut commands: Commands, mut time: ResMut<Time<Virtual>>) {
+    commands.remove_resource::<Playback>();
+    time.set_relative_speed(1.0);
+    time.set_max_delta(DEFAULT_MAX_DELTA);
+}
+
+fn playback_controls(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut playback: ResMut<Playback>,
+) {
+    const SEEK_STEPS: u32 = (10.0 * FIXED_HZ) as u32;
+
+    if keys.just_pressed(KeyCode::KeyF) {
+        playback.fast_forward = !playback.fast_forward;
+    }
+
+    let target = playback.seek.unwrap_or(playback.step);
+    if keys.just_pressed(KeyCode::ArrowRight) {
+        playback.seek = Some((target + SEEK_STEPS).min(playback.length));
+    } else if keys.just_pressed(KeyCode::ArrowLeft) {
+        let target = target.saturating_sub(SEEK_STEPS);
+        playback.seek = Some(target);
+        // Steps can't be undone, going back plays the replay again from the start
+        if target < playback.step {
+            commands.add(restart_run);
+        }
+    }
+}
+
+/// Runs more fixed steps per frame while fast forwarding or seeking, on top of the slow motion
+fn playback_speed(
+    playback: Res<Playback>,
+    power_ups: Res<ActivePowerUps>,
+    mut time: ResMut<Time<Virtual>>,
+) {
+    const FAST_FORWARD_SPEED: f32 = 4.0;
+    const SEEK_SPEED: f32 = 30.0;
+
+    let (speed, max_delta) = if playback.seek.is_some() {
+        (SEEK_SPEED, Duration::from_secs(1))
+    } else if playback.fast_forward {
+        (FAST_FORWARD_SPEED, DEFAULT_MAX_DELTA)
+    } else {
+        (1.0, DEFAULT_MAX_DELTA)
+    };
+    let speed = speed * power_ups.time_speed();
+
+    if time.relative_speed() != speed {
+        time.set_relative_speed(speed);
+        time.set_max_delta(max_delta);
+    }
+}
+
+#[derive(Component)]
+struct PlaybackText;
+
+fn setup_playback_ui(mut commands: Commands, playback: Option<Res<Playback>>) {
+    if playback.is_none() {
+        return;
+    }
+
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 30.0,
+                color: Color::srgba(0.7, 0.7, 0.7, 1.0),
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(40.0),
+            bottom: Val::Px(40.0),
+            ..default()
+        }),
+        PlaybackText,
+        RunEntity,
+    ));
+}
+
+fn update_playback_text(playback: Res<Playback>, mut text_q: Query<&mut Text, With<PlaybackText>>) {
+    let time = |step: u32| {
+        let seconds = (step as f64 / FIXED_HZ) as u32;
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    };
+    let speed = if playback.seek.is_some() {
+        " (seeking)"
+    } else if playback.fast_forward {
+        " (fast forward)"
+    } else {
+        ""
+    };
+
+    for mut text in text_q.iter_mut() {
+        text.sections[0].value = format!(
+            "REPLAY {} / {}{speed}\n[F] Fast forward, [Left/Right] Seek",
+            time(playback.step),
+            time(playback.length),
+        );
+    }
+}
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, play_replay_arg)
+            .add_systems(StartRun, (reset_replay, setup_playback_ui))
+            .add_systems(
+                FixedUpdate,
+                (
+                    record_inputs.run_if(resource_exists::<Recording>),
+                    play_inputs.run_if(resource_exists::<Playback>),
+                )
+                    .in_set(ApplyInputSet)
+                    .run_if(in_state(GameState::InGame)),
+            )
+            .add_systems(
+                Update,
+                watch_last_replay.run_if(in_state(GameState::MainMenu)),
+            )
+            .add_systems(
+                Update,
+                (playback_controls, playback_speed, update_playback_text)
+                    .chain()
+                    .run_if(in_state(GameState::InGame))
+                    .run_if(resource_exists::<Playback>),
+            )
+            .add_systems(
+                OnEnter(GameState::GameOver),
+                (save_recording, stop_playback),
+            )
+            .add_systems(
+                OnTransition {
+                    exited: GameState::Paused,
+                    entered: GameState::MainMenu,
+                },
+                (save_recording, stop_playback),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pack_round_trip() {
+        let input = PlayerInput {
+            aim: Some(Vec2::from_angle(2.0)),
+            thrust: true,
+            boost: true,
+            toggle_link: true,
+            ..default()
+        };
+        let unpacked = PackedInput::pack(&input).unpack(true);
+        assert!(
+            unpacked
+                .aim
+                .unwrap()
+                .angle_between(input.aim.unwrap())
+                .abs()
+                < TAU / AIM_STEPS as f32
+        );
+        assert!(unpacked.thrust && !unpacked.brake && unpacked.boost && unpacked.toggle_link);
+        assert!(unpacked.pause);
+    }
+
+    #[test]
+    fn pack_without_aim() {
+        let input = PlayerInput {
+            brake: true,
+            ..default()
+        };
+        assert_eq!(PackedInput::pack(&input), PackedInput(BRAKE_BIT));
+        let unpacked = PackedInput::pack(&input).unpack(false);
+        assert_eq!(unpacked.aim, None);
+        assert!(unpacked.brake && !unpacked.thrust);
+    }
+
+    #[test]
+    fn pack_aim_wraps_around() {
+        // Angles rounding up to a full turn go back to zero instead of overflowing into the flags
+        let input = PlayerInput {
+            aim: Some(Vec2::from_angle(-0.0001)),
+            ..default()
+        };
+        assert_eq!(PackedInput::pack(&input), PackedInput(AIM_BIT));
+    }
+
+    #[test]
+    fn identical_steps_are_run_length_encoded() {
+        let (a, b) = (
+            vec![PackedInput(1), PackedInput(2)],
+            vec![PackedInput(3), PackedInput(2)],
+        );
+        let steps = [&a, &a, &a, &b, &a].map(Vec::clone);
+        let mut replay = Replay::default();
+        for inputs in &steps {
+            replay.push(inputs.clone());
+        }
+        assert_eq!(
+            replay.steps,
+            [(3, a.clone()), (1, b.clone()), (1, a.clone())]
+        );
+        assert_eq!(replay.len(), 5);
+
+        let mut playback = Playback::new(replay);
+        let mut played = Vec::new();
+        while let Some(inputs) = playback.next() {
+            played.push(inputs.to_vec());
+        }
+        assert_eq!(played, steps);
+        assert_eq!(playback.step, playback.length);
+
+        playback.rewind();
+        assert_eq!(playback.next(), Some(a.as_slice()));
+    }
+}
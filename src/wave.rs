@@ -0,0 +1,256 @@
+use bevy::prelude::*;
+use bevy_prng::WyRand;
+use bevy_rand::resource::GlobalEntropy;
+use serde::Deserialize;
+
+use crate::asteroid::{
+    spawn_spawner, Asteroid, AsteroidSpawner, FiringPattern, PortalEffect, SpawnerDefinition,
+    SpawnerMotion,
+};
+use crate::config::init_ron_resource;
+use crate::{GameState, GameplaySet, StartRun};
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct WaveDefinition {
+    /// Indices of the spawners firing during the wave, every spawner when empty
+    #[serde(default)]
+    pub spawners: Vec<usize>,
+    pub asteroids_per_spawner: u32,
+    pub min_speed: f32,
+    pub max_speed: f32,
+    /// Seconds before the first asteroid and between two asteroids of a spawner
+    pub delay: f32,
+    /// Seconds of calm once the wave is cleared
+    pub break_duration: f32,
+    /// Spawners opening when the wave starts, numbered after the ones already opened
+    #[serde(default)]
+    pub open_spawners: Vec<SpawnerDefinition>,
+}
+
+/// Waves of a game, loaded from `assets/config/game.waves.ron`
+#[derive(Asset, Resource, TypePath, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct WaveList {
+    /// Spawners open when a game starts, opening again once they have all been destroyed
+    pub spawners: Vec<SpawnerDefinition>,
+    pub waves: Vec<WaveDefinition>,
+    /// Once every wave has been played they start over with faster asteroids...
+    pub loop_speed_factor: f32,
+    /// ...and shorter delays
+    pub loop_delay_factor: f32,
+}
+
+impl Default for WaveList {
+    fn default() -> Self {
+        Self {
+            spawners: [(-1.0, -1.0), (-1.0, 1.0), (1.0, -1.0), (1.0, 1.0)]
+                .into_iter()
+                .map(|(x, y)| SpawnerDefinition {
+                    position: Vec2::new(x, y) * 200.0,
+                    motion: SpawnerMotion::Fixed,
+                    health: 3,
+                    pattern: FiringPattern::Random,
+                })
+                .collect(),
+            waves: vec![WaveDefinition {
+                spawners: vec![],
+                asteroids_per_spawner: 5,
+                min_speed: 100.0,
+                max_speed: 150.0,
+                delay: 2.0,
+                break_duration: 3.0,
+                open_spawners: vec![],
+            }],
+            loop_speed_factor: 1.1,
+            loop_delay_factor: 0.85,
+        }
+    }
+}
+
+#[derive(Event, Debug, Clone, Copy)]
+pub struct WaveStarted(pub u32);
+
+#[derive(Event, Debug, Clone, Copy)]
+pub struct WaveCleared(pub u32);
+
+/// Sent along with [`WaveCleared`] when the players destroyed every spawner
+#[derive(Event, Debug, Clone, Copy)]
+pub struct LevelCleared;
+
+#[derive(Debug)]
+enum WaveState {
+    Break(Timer),
+    Running,
+}
+
+#[derive(Resource, Debug)]
+struct WaveDirector {
+    /// Number of the current wave, starting at 1
+    wave: u32,
+    state: WaveState,
+    /// Index given to the next spawner opened by a wave
+    next_spawner_index: usize,
+}
+
+impl Default for WaveDirector {
+    fn default() -> Self {
+        Self {
+            wave: 0,
+            next_spawner_index: 0,
+            state: WaveState::Break(Timer::from_seconds(2.0, TimerMode::Once)),
+        }
+    }
+}
+
+/// Wave counter shown next to the score
+#[derive(Component)]
+pub struct WaveText;
+
+fn reset_wave_director(mut commands: Commands, wave_list: Res<WaveList>) {
+    commands.insert_resource(WaveDirector {
+        next_spawner_index: wave_list.spawners.len(),
+        ..default()
+    });
+}
+
+fn wave_director(
+    mut commands: Commands,
+    mut director: ResMut<WaveDirector>,
+    wave_list: Res<WaveList>,
+    time: Res<Time>,
+    mut spawners: Query<&mut AsteroidSpawner>,
+    asteroids: Query<(), With<Asteroid>>,
+    asset_server: Res<AssetServer>,
+    portal_effect: Res<PortalEffect>,
+    mut rng: ResMut<GlobalEntropy<WyRand>>,
+    mut started_writer: EventWriter<WaveStarted>,
+    mut cleared_writer: EventWriter<WaveCleared>,
+    mut level_writer: EventWriter<LevelCleared>,
+) {
+    if wave_list.waves.is_empty() {
+        return;
+    }
+    let definition = |wave: u32| &wave_list.waves[(wave as usize - 1) % wave_list.waves.len()];
+
+    match &mut director.state {
+        WaveState::Break(timer) => {
+            if !timer.tick(time.delta()).finished() {
+                return;
+            }
+
+            director.wave += 1;
+            let wave = definition(director.wave);
+            let loop_count = (director.wave as usize - 1) / wave_list.waves.len();
+            let speed_factor = wave_list.loop_speed_factor.powi(loop_count as i32);
+            let delay_factor = wave_list.loop_delay_factor.powi(loop_count as i32);
+
+            const MIN_SPAWNER_DELAY: f32 = 0.3;
+            let arm = |spawner: &mut AsteroidSpawner| {
+                if !wave.spawners.is_empty() && !wave.spawners.contains(&spawner.index) {
+                    return;
+                }
+                spawner.remaining = wave.asteroids_per_spawner;
+                spawner.min_speed = wave.min_speed * speed_factor;
+                spawner.max_speed = wave.max_speed * speed_factor;
+                spawner.timer = Timer::from_seconds(
+                    (wave.delay * delay_factor).max(MIN_SPAWNER_DELAY),
+                    TimerMode::Repeating,
+                );
+            };
+            for mut spawner in spawners.iter_mut() {
+                arm(&mut spawner);
+            }
+
+            // A cleared level opens its spawners again, then the wave opens its own ones
+            let reopened = spawners
+                .is_empty()
+                .then(|| wave_list.spawners.iter().enumerate())
+                .into_iter()
+                .flatten();
+            let first_index = director.next_spawner_index;
+            let opened = wave
+                .open_spawners
+                .iter()
+                .enumerate()
+                .map(|(i, definition)| (first_index + i, definition));
+            for (index, definition) in reopened.chain(opened) {
+                let mut spawner = AsteroidSpawner::idle(index, definition);
+                arm(&mut spawner);
+                spawn_spawner(
+                    &mut commands,
+                    &asset_server,
+                    &portal_effect,
+                    &mut rng,
+                    spawner,
+                    definition,
+                    true,
+                );
+            }
+            director.next_spawner_index += wave.open_spawners.len();
+
+            director.state = WaveState::Running;
+            started_writer.send(WaveStarted(director.wave));
+        }
+        WaveState::Running => {
+            let spawning = spawners.iter().any(|spawner| spawner.remaining > 0);
+            if spawning || !asteroids.is_empty() {
+                return;
+            }
+
+            let break_duration = definition(director.wave).break_duration;
+            director.state = WaveState::Break(Timer::from_seconds(break_duration, TimerMode::Once));
+            cleared_writer.send(WaveCleared(director.wave));
+            if spawners.is_empty() {
+                level_writer.send(LevelCleared);
+            }
+        }
+    }
+}
+
+fn update_wave_text(
+    mut started_reader: EventReader<WaveStarted>,
+    mut cleared_reader: EventReader<WaveCleared>,
+    mut level_reader: EventReader<LevelCleared>,
+    mut text_q: Query<&mut Text, With<WaveText>>,
+) {
+    let started = started_reader
+        .read()
+        .last()
+        .map(|WaveStarted(wave)| format!("WAVE {wave}"));
+    let cleared = cleared_reader
+        .read()
+        .last()
+        .map(|WaveCleared(wave)| format!("WAVE {wave} CLEARED"));
+
+    let level = level_reader
+        .read()
+        .last()
+        .map(|_| "LEVEL CLEARED".to_string());
+
+    let Some(value) = started.or(level).or(cleared) else {
+        return;
+    };
+    for mut text in text_q.iter_mut() {
+        text.sections[0].value.clone_from(&value);
+    }
+}
+
+pub struct WavePlugin;
+
+impl Plugin for WavePlugin {
+    fn build(&self, app: &mut App) {
+        init_ron_resource::<WaveList>(app, "config/game.waves.ron", "waves.ron");
+        app.add_event::<WaveStarted>()
+            .add_event::<WaveCleared>()
+            .add_event::<LevelCleared>()
+            .init_resource::<WaveDirector>()
+            .add_systems(StartRun, reset_wave_director)
+            .add_systems(
+                FixedUpdate,
+                wave_director
+                    .in_set(GameplaySet::Waves)
+                    .run_if(in_state(GameState::InGame)),
+            )
+            .add_systems(Update, update_wave_text.run_if(in_state(GameState::InGame)));
+    }
+}